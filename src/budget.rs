@@ -0,0 +1,257 @@
+use serde::Deserialize;
+use std::{collections::BTreeMap, fmt, fs, io, path::Path};
+
+use crate::cli::SizeMetric;
+use crate::scanner::{format_size, ModuleInfo};
+
+/// Size limits a scan has to stay within.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Budget {
+    /// Cap on the combined size of all installed packages
+    #[serde(default, deserialize_with = "deserialize_optional_size")]
+    pub total: Option<u64>,
+    /// Cap applied to every installed package
+    #[serde(default, deserialize_with = "deserialize_optional_size")]
+    pub any_package: Option<u64>,
+    /// Caps for specific packages, keyed by package name
+    #[serde(default, deserialize_with = "deserialize_size_map")]
+    pub packages: BTreeMap<String, u64>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Violation {
+    Total { actual: u64, limit: u64 },
+    AnyPackage { path: String, actual: u64, limit: u64 },
+    Package { path: String, actual: u64, limit: u64 },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::Total { actual, limit } => write!(
+                f,
+                "total size {} exceeds the budget of {}",
+                format_size(*actual),
+                format_size(*limit)
+            ),
+            Violation::AnyPackage { path, actual, limit } => write!(
+                f,
+                "{} is {}, over the per-package limit of {}",
+                path,
+                format_size(*actual),
+                format_size(*limit)
+            ),
+            Violation::Package { path, actual, limit } => write!(
+                f,
+                "{} is {}, over its budget of {}",
+                path,
+                format_size(*actual),
+                format_size(*limit)
+            ),
+        }
+    }
+}
+
+impl Budget {
+    pub fn from_file(path: &Path) -> io::Result<Budget> {
+        let content = fs::read_to_string(path)?;
+        serde_json::from_str(&content).map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid budget file {}: {}", path.display(), err),
+            )
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.total.is_none() && self.any_package.is_none() && self.packages.is_empty()
+    }
+
+    /// Evaluates every rule against the sizes given by `metric` and returns
+    /// all violations, largest offenders first.
+    pub fn check(&self, modules: &[ModuleInfo], metric: SizeMetric) -> Vec<Violation> {
+        let mut violations = Vec::new();
+
+        if let Some(limit) = self.total {
+            let actual = modules.iter().map(|m| m.size_by(metric)).sum();
+            if actual > limit {
+                violations.push(Violation::Total { actual, limit });
+            }
+        }
+
+        let mut sorted: Vec<&ModuleInfo> = modules.iter().collect();
+        sorted.sort_by_key(|m| std::cmp::Reverse(m.size_by(metric)));
+
+        for module in sorted {
+            let size = module.size_by(metric);
+            // A package's own budget replaces the one for any package, it may be larger
+            if let Some(&limit) = self.packages.get(&module.name) {
+                if size > limit {
+                    violations.push(Violation::Package {
+                        path: module.path.clone(),
+                        actual: size,
+                        limit,
+                    });
+                }
+                continue;
+            }
+            if let Some(limit) = self.any_package {
+                if size > limit {
+                    violations.push(Violation::AnyPackage {
+                        path: module.path.clone(),
+                        actual: size,
+                        limit,
+                    });
+                }
+            }
+        }
+
+        violations
+    }
+}
+
+/// Parses sizes such as `512`, `40MB`, `1.5 GiB` or `300k`. Units are binary,
+/// matching `format_size`.
+pub fn parse_size(text: &str) -> Result<u64, String> {
+    let text = text.trim();
+    let split = text
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("invalid size '{}'", text))?;
+
+    let multiplier: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" | "kib" => 1024,
+        "m" | "mb" | "mib" => 1024 * 1024,
+        "g" | "gb" | "gib" => 1024 * 1024 * 1024,
+        _ => return Err(format!("unknown size unit in '{}'", text)),
+    };
+    Ok((number * multiplier as f64).round() as u64)
+}
+
+/// Parses a `name=size` pair given on the command line.
+pub fn parse_package_budget(text: &str) -> Result<(String, u64), String> {
+    // Split on the last '=' so scoped names stay intact
+    let (name, size) = text
+        .rsplit_once('=')
+        .ok_or_else(|| format!("expected NAME=SIZE, got '{}'", text))?;
+    Ok((name.to_string(), parse_size(size)?))
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SizeValue {
+    Bytes(u64),
+    Text(String),
+}
+
+impl SizeValue {
+    fn into_bytes<E: serde::de::Error>(self) -> Result<u64, E> {
+        match self {
+            SizeValue::Bytes(bytes) => Ok(bytes),
+            SizeValue::Text(text) => parse_size(&text).map_err(E::custom),
+        }
+    }
+}
+
+fn deserialize_optional_size<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Option::<SizeValue>::deserialize(deserializer)?
+        .map(SizeValue::into_bytes)
+        .transpose()
+}
+
+fn deserialize_size_map<'de, D>(deserializer: D) -> Result<BTreeMap<String, u64>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    BTreeMap::<String, SizeValue>::deserialize(deserializer)?
+        .into_iter()
+        .map(|(name, size)| Ok((name, size.into_bytes()?)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::{scan_modules_dir, ScanOptions};
+    use std::fs::File;
+    use std::io::Write;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("512"), Ok(512));
+        assert_eq!(parse_size("2KB"), Ok(2048));
+        assert_eq!(parse_size("1.5 MiB"), Ok(1572864));
+        assert_eq!(parse_size("40mb"), Ok(40 * 1024 * 1024));
+        assert_eq!(parse_size("1G"), Ok(1024 * 1024 * 1024));
+        assert!(parse_size("lots").is_err());
+        assert!(parse_size("5 parsecs").is_err());
+        assert_eq!(parse_package_budget("@babel/core=1MB"), Ok(("@babel/core".to_string(), 1024 * 1024)));
+    }
+
+    #[test]
+    fn test_budget_check() -> io::Result<()> {
+        let temp_dir = tempdir()?;
+        for (name, size) in [("big", 3000), ("@s/medium", 2000), ("small", 100)] {
+            let module_path = temp_dir.path().join(name);
+            fs::create_dir_all(&module_path)?;
+            File::create(module_path.join("index.js"))?.write_all(&vec![b'a'; size])?;
+        }
+        let modules = scan_modules_dir(temp_dir.path(), &ScanOptions::default())?.modules;
+
+        let budget_path = temp_dir.path().join("budget.json");
+        File::create(&budget_path)?
+            .write_all(br#"{"total": "4KB", "any_package": 2500, "packages": {"@s/medium": "1KB"}}"#)?;
+        let budget = Budget::from_file(&budget_path)?;
+
+        assert_eq!(
+            budget.check(&modules, SizeMetric::Apparent),
+            vec![
+                Violation::Total { actual: 5100, limit: 4096 },
+                Violation::AnyPackage { path: "node_modules/big".to_string(), actual: 3000, limit: 2500 },
+                Violation::Package { path: "node_modules/@s/medium".to_string(), actual: 2000, limit: 1024 },
+            ]
+        );
+
+        assert!(Budget::default().check(&modules, SizeMetric::Apparent).is_empty());
+
+        // Small files take up a whole block on disk
+        let mut modules = modules;
+        for module in &mut modules {
+            module.disk_size = 4096;
+        }
+        assert_eq!(
+            budget.check(&modules, SizeMetric::Disk)[0],
+            Violation::Total { actual: 3 * 4096, limit: 4096 }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_package_budget_overrides_any_package() {
+        let module = |name: &str, size| ModuleInfo {
+            name: name.to_string(),
+            path: format!("node_modules/{}", name),
+            size,
+            ..ModuleInfo::default()
+        };
+        let modules = [module("foo", 500 * 1024), module("bar", 3000)];
+        let budget = Budget {
+            any_package: Some(2500),
+            packages: BTreeMap::from([("foo".to_string(), 1024 * 1024)]),
+            ..Budget::default()
+        };
+        assert_eq!(
+            budget.check(&modules, SizeMetric::Apparent),
+            vec![Violation::AnyPackage { path: "node_modules/bar".to_string(), actual: 3000, limit: 2500 }]
+        );
+    }
+}
@@ -0,0 +1,278 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    env, fs, io,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use crate::lockfile;
+use crate::scanner::{get_fingerprint, LinkedFile, ModuleInfo};
+
+/// Version of the cache file layout, caches written by other versions are ignored.
+const CACHE_VERSION: u32 = 2;
+
+/// What a package directory looked like when it was measured.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Fingerprint {
+    /// Modification time of the package directory, in nanoseconds since the epoch
+    pub root_mtime: u64,
+    /// Inode of the package directory, a reinstalled package gets a new one
+    pub root_inode: u64,
+    /// Sum of the modification times of every directory in the package
+    pub mtimes: u64,
+    /// Number of files, directories and links in the package
+    pub entries: u64,
+}
+
+impl Fingerprint {
+    pub fn set_root(&mut self, metadata: &fs::Metadata) {
+        self.root_mtime = mtime_nanos(metadata);
+        self.root_inode = inode(metadata);
+    }
+
+    pub fn add_dir(&mut self, metadata: &fs::Metadata) {
+        self.mtimes = self.mtimes.wrapping_add(mtime_nanos(metadata));
+    }
+}
+
+fn mtime_nanos(metadata: &fs::Metadata) -> u64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |since| since.as_nanos() as u64)
+}
+
+#[cfg(unix)]
+fn inode(metadata: &fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    metadata.ino()
+}
+
+#[cfg(not(unix))]
+fn inode(_metadata: &fs::Metadata) -> u64 {
+    0
+}
+
+/// A package as measured by an earlier scan.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedPackage {
+    pub fingerprint: Fingerprint,
+    /// The package without its hard-linked files, which are counted for
+    /// whichever package owns them in the scan using the cache
+    pub module: ModuleInfo,
+    pub links: Vec<LinkedFile>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CacheFile {
+    version: u32,
+    node_modules: PathBuf,
+    /// Hash of the lockfile at the time of the scan, a different lockfile
+    /// means something was installed and nothing cached is trusted
+    lockfile_hash: Option<u64>,
+    /// Packages by install path
+    packages: HashMap<String, CachedPackage>,
+}
+
+/// The packages measured by the previous scan of a `node_modules` directory.
+#[derive(Debug)]
+pub struct ScanCache {
+    file: PathBuf,
+    node_modules: PathBuf,
+    lockfile_hash: Option<u64>,
+    previous: CacheFile,
+}
+
+impl ScanCache {
+    /// Loads the cache of the `node_modules` directory at the canonical path
+    /// `node_modules`. A missing, unreadable or outdated cache counts as
+    /// empty, and so does one written with a different lockfile.
+    pub fn open(cache_dir: &Path, node_modules: &Path) -> ScanCache {
+        let file = cache_dir.join(format!("{:016x}.json", fnv1a(node_modules.as_os_str().as_encoded_bytes())));
+        let lockfile_hash = node_modules
+            .parent()
+            .and_then(lockfile::find)
+            .and_then(|path| fs::read(path).ok())
+            .map(|content| fnv1a(&content));
+        let previous = fs::read(&file)
+            .ok()
+            .and_then(|content| serde_json::from_slice::<CacheFile>(&content).ok())
+            .filter(|cache| {
+                cache.version == CACHE_VERSION
+                    && cache.node_modules == node_modules
+                    && cache.lockfile_hash == lockfile_hash
+            })
+            .unwrap_or_default();
+        ScanCache {
+            file,
+            node_modules: node_modules.to_path_buf(),
+            lockfile_hash,
+            previous,
+        }
+    }
+
+    /// Returns the package installed at `install_path` as measured last time,
+    /// unless any directory in `dir` changed since.
+    pub fn lookup(&self, install_path: &str, dir: &Path) -> Option<CachedPackage> {
+        let cached = self.previous.packages.get(install_path)?;
+        // The package directory alone already rules out most reinstalls
+        // without walking the whole package
+        let mut root = Fingerprint::default();
+        root.set_root(&fs::metadata(dir).ok()?);
+        if (root.root_mtime, root.root_inode) != (cached.fingerprint.root_mtime, cached.fingerprint.root_inode) {
+            return None;
+        }
+        if get_fingerprint(dir).ok()? != cached.fingerprint {
+            return None;
+        }
+        Some(cached.clone())
+    }
+
+    /// Replaces the cache with the packages of the current scan.
+    pub fn save(&self, packages: HashMap<String, CachedPackage>) -> io::Result<()> {
+        let cache = CacheFile {
+            version: CACHE_VERSION,
+            node_modules: self.node_modules.clone(),
+            lockfile_hash: self.lockfile_hash,
+            packages,
+        };
+        if let Some(dir) = self.file.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        // Written next to the cache and renamed, so a concurrent scan never reads half a file
+        let partial = self.file.with_extension("json.partial");
+        fs::write(&partial, serde_json::to_vec(&cache)?)?;
+        fs::rename(&partial, &self.file)
+    }
+}
+
+/// Where scan caches are kept: `$XDG_CACHE_HOME/node-size`, `~/.cache/node-size`
+/// or `%LOCALAPPDATA%\node-size` on Windows.
+pub fn default_dir() -> Option<PathBuf> {
+    let base = match env::var_os("XDG_CACHE_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None if cfg!(windows) => PathBuf::from(env::var_os("LOCALAPPDATA")?),
+        None => PathBuf::from(env::var_os("HOME")?).join(".cache"),
+    };
+    Some(base.join("node-size"))
+}
+
+/// Deletes every cached scan in `cache_dir` and returns how many there were.
+pub fn clear(cache_dir: &Path) -> io::Result<usize> {
+    let entries = match fs::read_dir(cache_dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(err) => return Err(err),
+    };
+    let mut removed = 0;
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().is_some_and(|extension| extension == "json" || extension == "partial") {
+            fs::remove_file(&path)?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+/// 64-bit FNV-1a, stable across runs and platforms unlike the std hasher.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::{scan_modules_dir, ScanOptions};
+    use std::fs::{File, OpenOptions};
+    use std::io::Write;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_unchanged_packages_come_from_cache() -> io::Result<()> {
+        let temp_dir = tempdir()?;
+        let node_modules = temp_dir.path().join("node_modules");
+        fs::create_dir_all(node_modules.join("a/lib"))?;
+        File::create(node_modules.join("a/lib/index.js"))?.write_all(&[b'a'; 100])?;
+        let options = ScanOptions {
+            cache_dir: Some(temp_dir.path().join("cache")),
+            ..ScanOptions::default()
+        };
+        let size = |options: &ScanOptions| scan_modules_dir(&node_modules, options).map(|output| output.modules[0].size);
+        assert_eq!(size(&options)?, 100);
+
+        // Without the cache everything is measured again
+        OpenOptions::new().append(true).open(node_modules.join("a/lib/index.js"))?.write_all(&[b'a'; 10])?;
+        assert_eq!(size(&ScanOptions::default())?, 110);
+
+        // A new file deep inside the package is noticed
+        File::create(node_modules.join("a/lib/extra.js"))?.write_all(&[b'a'; 5])?;
+        assert_eq!(size(&options)?, 115);
+
+        assert_eq!(clear(&temp_dir.path().join("cache"))?, 1);
+        assert_eq!(clear(&temp_dir.path().join("missing"))?, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_changes_are_noticed_with_a_lockfile() -> io::Result<()> {
+        let temp_dir = tempdir()?;
+        let node_modules = temp_dir.path().join("node_modules");
+        fs::create_dir_all(node_modules.join("a/lib"))?;
+        File::create(node_modules.join("a/lib/index.js"))?.write_all(&[b'a'; 100])?;
+        fs::write(temp_dir.path().join("package-lock.json"), r#"{"lockfileVersion": 3, "packages": {}}"#)?;
+        let options = ScanOptions {
+            cache_dir: Some(temp_dir.path().join("cache")),
+            ..ScanOptions::default()
+        };
+        let size = || scan_modules_dir(&node_modules, &options).map(|output| output.modules[0].size);
+        assert_eq!(size()?, 100);
+
+        // Only a/lib changes, the package directory and the lockfile stay the same
+        File::create(node_modules.join("a/lib/extra.js"))?.write_all(&[b'a'; 9000])?;
+        assert_eq!(size()?, 9100);
+
+        // An install rewrote a file in place, which no directory shows, but
+        // it also changed the lockfile
+        OpenOptions::new().append(true).open(node_modules.join("a/lib/index.js"))?.write_all(&[b'a'; 50])?;
+        fs::write(temp_dir.path().join("package-lock.json"), r#"{"lockfileVersion": 3, "packages": {"": {}}}"#)?;
+        assert_eq!(size()?, 9150);
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_cached_packages_keep_their_hard_links() -> io::Result<()> {
+        let temp_dir = tempdir()?;
+        let node_modules = temp_dir.path().join("node_modules");
+        for name in ["a", "b"] {
+            fs::create_dir_all(node_modules.join(name))?;
+        }
+        File::create(node_modules.join("a/index.js"))?.write_all(&[b'a'; 3000])?;
+        fs::hard_link(node_modules.join("a/index.js"), node_modules.join("b/index.js"))?;
+        let options = ScanOptions {
+            cache_dir: Some(temp_dir.path().join("cache")),
+            ..ScanOptions::default()
+        };
+        let totals = || {
+            scan_modules_dir(&node_modules, &options).map(|output| {
+                let size = output.modules.iter().map(|m| m.size).sum::<u64>();
+                let shared = output.modules.iter().map(|m| m.shared_size).sum::<u64>();
+                (size, shared)
+            })
+        };
+        assert_eq!(totals()?, (3000, 3000));
+
+        // Only b is measured again, a comes from the cache and still shares
+        // the file with it
+        File::create(node_modules.join("b/extra.js"))?.write_all(&[b'b'; 7])?;
+        assert_eq!(totals()?, (3007, 3000));
+        File::create(node_modules.join("a/extra.js"))?.write_all(&[b'a'; 5])?;
+        assert_eq!(totals()?, (3012, 3000));
+        Ok(())
+    }
+}
@@ -0,0 +1,430 @@
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use std::{num::NonZeroUsize, path::PathBuf, thread};
+
+use crate::budget::{parse_package_budget, parse_size};
+use crate::cache;
+use crate::scanner::ScanOptions;
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "node-size",
+    version,
+    about = "Analyze node_modules sizes",
+    args_conflicts_with_subcommands = true
+)]
+pub struct Cli {
+    #[command(flatten)]
+    pub scan: ScanArgs,
+
+    /// Print a table to stdout instead of opening the terminal UI
+    #[arg(long)]
+    pub no_tui: bool,
+
+    #[command(flatten)]
+    pub output: OutputArgs,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Open the interactive terminal UI (the default)
+    Tui(ScanArgs),
+    /// Print the scan result to stdout
+    Report(ReportArgs),
+    /// Check the scan against size budgets, exiting non-zero on violations
+    Check(CheckArgs),
+    /// Save the scan to a snapshot file for later comparison
+    Snapshot(SnapshotArgs),
+    /// Compare a snapshot with another snapshot or with a live scan
+    Diff(DiffArgs),
+    /// Explain why a package is installed by listing the dependency chains leading to it
+    Why(WhyArgs),
+    /// Delete the cached results of earlier scans
+    ClearCache,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct ScanArgs {
+    /// Project directory, or the node_modules directory itself
+    #[arg(default_value = ".")]
+    pub path: PathBuf,
+
+    /// Only count the packages `npm install --omit=dev` would install
+    #[arg(long)]
+    pub prod: bool,
+
+    /// Size that decides the sort order and is checked against budgets
+    #[arg(long, value_enum, default_value_t = SizeMetric::Apparent)]
+    pub metric: SizeMetric,
+
+    /// How packages symlinked from outside node_modules are measured
+    #[arg(long, value_enum, default_value_t = SymlinkPolicy::Follow)]
+    pub symlinks: SymlinkPolicy,
+
+    /// Number of threads measuring packages [default: number of CPUs]
+    #[arg(short = 'j', long, value_parser = clap::value_parser!(u16).range(1..))]
+    pub threads: Option<u16>,
+
+    /// Measure every package again instead of reusing unchanged ones from the last scan
+    #[arg(long)]
+    pub no_cache: bool,
+}
+
+impl ScanArgs {
+    /// Resolves the `node_modules` directory to scan.
+    pub fn node_modules_dir(&self) -> PathBuf {
+        if self.path.file_name().is_some_and(|name| name == "node_modules") {
+            self.path.clone()
+        } else {
+            self.path.join("node_modules")
+        }
+    }
+
+    /// The project directory holding `package.json` and the lockfile.
+    pub fn project_dir(&self) -> PathBuf {
+        match self.node_modules_dir().parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => PathBuf::from("."),
+        }
+    }
+
+    pub fn scan_options(&self) -> ScanOptions {
+        let threads = match self.threads {
+            Some(threads) => threads.into(),
+            None => thread::available_parallelism().map_or(1, NonZeroUsize::get),
+        };
+        ScanOptions {
+            symlinks: self.symlinks,
+            threads,
+            progress: None,
+            cache_dir: if self.no_cache { None } else { cache::default_dir() },
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct ReportArgs {
+    #[command(flatten)]
+    pub scan: ScanArgs,
+
+    #[command(flatten)]
+    pub output: OutputArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct CheckArgs {
+    #[command(flatten)]
+    pub scan: ScanArgs,
+
+    /// JSON file with `total`, `any_package` and `packages` budgets
+    #[arg(long)]
+    pub budget: Option<PathBuf>,
+
+    /// Maximum combined size of all packages, e.g. 500MB
+    #[arg(long, value_parser = parse_size)]
+    pub max_total: Option<u64>,
+
+    /// Maximum size of any single package
+    #[arg(long, value_parser = parse_size)]
+    pub max_any: Option<u64>,
+
+    /// Maximum size of a named package as NAME=SIZE, may be repeated
+    #[arg(long, value_parser = parse_package_budget)]
+    pub max_package: Vec<(String, u64)>,
+}
+
+#[derive(Debug, Args)]
+pub struct SnapshotArgs {
+    #[command(flatten)]
+    pub scan: ScanArgs,
+
+    /// Snapshot file to write
+    #[arg(short, long, default_value = "node-size-snapshot.json")]
+    pub output: PathBuf,
+}
+
+#[derive(Debug, Args)]
+pub struct WhyArgs {
+    /// Package name, optionally with a version as in `lodash@4.17.21`
+    pub package: String,
+
+    #[command(flatten)]
+    pub scan: ScanArgs,
+
+    /// Maximum number of dependency chains to list per installed copy
+    #[arg(long, default_value_t = 10)]
+    pub limit: usize,
+}
+
+#[derive(Debug, Args)]
+pub struct DiffArgs {
+    /// Snapshot to compare against
+    pub old: PathBuf,
+
+    /// Newer snapshot, the project is scanned when omitted
+    pub new: Option<PathBuf>,
+
+    /// Project directory to scan when no newer snapshot is given
+    #[arg(long, default_value = ".")]
+    pub project: PathBuf,
+
+    /// Only count production packages when scanning the project
+    #[arg(long)]
+    pub prod: bool,
+
+    /// Size that is compared between the scans
+    #[arg(long, value_enum, default_value_t = SizeMetric::Apparent)]
+    pub metric: SizeMetric,
+
+    /// Output format
+    #[arg(long, value_enum, default_value_t = DiffFormat::Text)]
+    pub format: DiffFormat,
+
+    /// Show the differences in the terminal UI
+    #[arg(long, conflicts_with = "format")]
+    pub tui: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum DiffFormat {
+    Text,
+    Json,
+}
+
+#[derive(Debug, Args)]
+pub struct OutputArgs {
+    /// Output format, implies --no-tui
+    #[arg(long, value_enum)]
+    pub format: Option<OutputFormat>,
+
+    /// Write the output to a file instead of stdout, implies --no-tui
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+
+    /// Columns for csv and tsv output, comma separated
+    #[arg(long, value_enum, value_delimiter = ',', default_values_t = Column::ALL)]
+    pub columns: Vec<Column>,
+}
+
+impl OutputArgs {
+    /// Whether any output option was given, which means the TUI is not wanted.
+    pub fn is_requested(&self) -> bool {
+        self.format.is_some() || self.output.is_some()
+    }
+
+    pub fn format(&self) -> OutputFormat {
+        self.format.unwrap_or(OutputFormat::Table)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SizeMetric {
+    /// Sum of the file lengths
+    Apparent,
+    /// Disk space allocated in filesystem blocks, as reported by `du`
+    Disk,
+}
+
+impl SizeMetric {
+    pub fn label(self) -> &'static str {
+        match self {
+            SizeMetric::Apparent => "apparent size",
+            SizeMetric::Disk => "disk usage",
+        }
+    }
+}
+
+/// What to do with a package directory that is a symlink, as created by
+/// `npm link` or workspaces. Links to directories inside `node_modules` are
+/// always left to the real directory, which is counted where it lives.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum SymlinkPolicy {
+    /// Leave linked packages out
+    Skip,
+    /// Measure each link target once, later links to it are listed but not counted again
+    #[default]
+    Follow,
+    /// List linked packages without measuring their target
+    Link,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Human readable table
+    Table,
+    /// Versioned JSON document with the full scan result
+    Json,
+    /// Comma separated values, one row per module
+    Csv,
+    /// Tab separated values, one row per module
+    Tsv,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+#[value(rename_all = "snake_case")]
+pub enum Column {
+    Name,
+    Version,
+    Size,
+    DiskSize,
+    FilesCount,
+    DependencyCount,
+    License,
+    IsDevDependency,
+    DependencyKind,
+    Path,
+}
+
+impl Column {
+    pub const ALL: [Column; 10] = [
+        Column::Name,
+        Column::Version,
+        Column::Size,
+        Column::DiskSize,
+        Column::FilesCount,
+        Column::DependencyCount,
+        Column::License,
+        Column::IsDevDependency,
+        Column::DependencyKind,
+        Column::Path,
+    ];
+
+    /// Header name, identical to the value accepted by `--columns`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Column::Name => "name",
+            Column::Version => "version",
+            Column::Size => "size",
+            Column::DiskSize => "disk_size",
+            Column::FilesCount => "files_count",
+            Column::DependencyCount => "dependency_count",
+            Column::License => "license",
+            Column::IsDevDependency => "is_dev_dependency",
+            Column::DependencyKind => "dependency_kind",
+            Column::Path => "path",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_path_and_subcommands() {
+        let cli = Cli::parse_from(["node-size"]);
+        assert!(cli.command.is_none());
+        assert_eq!(cli.scan.node_modules_dir(), PathBuf::from("./node_modules"));
+        assert_eq!(cli.scan.project_dir(), PathBuf::from("."));
+
+        let cli = Cli::parse_from(["node-size", "../app", "--no-tui"]);
+        assert!(cli.no_tui);
+        assert_eq!(cli.scan.node_modules_dir(), PathBuf::from("../app/node_modules"));
+
+        let cli = Cli::parse_from(["node-size", "report", "/srv/app/node_modules", "--prod", "--symlinks", "skip"]);
+        match cli.command {
+            Some(Command::Report(args)) => {
+                assert_eq!(args.scan.node_modules_dir(), PathBuf::from("/srv/app/node_modules"));
+                assert_eq!(args.scan.project_dir(), PathBuf::from("/srv/app"));
+                assert!(args.scan.prod);
+                assert_eq!(args.scan.symlinks, SymlinkPolicy::Skip);
+            }
+            _ => panic!("Expected report subcommand"),
+        }
+    }
+
+    #[test]
+    fn test_parse_output_options() {
+        let cli = Cli::parse_from(["node-size", "--format", "json"]);
+        assert!(cli.output.is_requested());
+        assert_eq!(cli.output.format(), OutputFormat::Json);
+
+        let cli = Cli::parse_from(["node-size", "report", "-o", "scan.json"]);
+        match cli.command {
+            Some(Command::Report(args)) => {
+                assert_eq!(args.output.format(), OutputFormat::Table);
+                assert_eq!(args.output.output, Some(PathBuf::from("scan.json")));
+                assert_eq!(args.output.columns, Column::ALL);
+            }
+            _ => panic!("Expected report subcommand"),
+        }
+
+        let cli = Cli::parse_from(["node-size", "--format", "csv", "--columns", "name,files_count,size"]);
+        assert_eq!(cli.output.format(), OutputFormat::Csv);
+        assert_eq!(cli.output.columns, vec![Column::Name, Column::FilesCount, Column::Size]);
+    }
+
+    #[test]
+    fn test_parse_diff() {
+        let cli = Cli::parse_from(["node-size", "diff", "before.json", "--project", "app", "--tui", "--metric", "disk"]);
+        match cli.command {
+            Some(Command::Diff(args)) => {
+                assert_eq!(args.old, PathBuf::from("before.json"));
+                assert_eq!(args.new, None);
+                assert_eq!(args.project, PathBuf::from("app"));
+                assert_eq!(args.metric, SizeMetric::Disk);
+                assert!(args.tui);
+            }
+            _ => panic!("Expected diff subcommand"),
+        }
+        assert!(Cli::try_parse_from(["node-size", "diff", "a.json", "--tui", "--format", "json"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_why() {
+        let cli = Cli::parse_from(["node-size", "why", "@babel/core@7.24.0", "app", "--limit", "3"]);
+        match cli.command {
+            Some(Command::Why(args)) => {
+                assert_eq!(args.package, "@babel/core@7.24.0");
+                assert_eq!(args.scan.path, PathBuf::from("app"));
+                assert_eq!(args.scan.symlinks, SymlinkPolicy::Follow);
+                assert_eq!(args.limit, 3);
+            }
+            _ => panic!("Expected why subcommand"),
+        }
+    }
+
+    #[test]
+    fn test_parse_threads() {
+        let cli = Cli::parse_from(["node-size", "report", "-j", "4"]);
+        match cli.command {
+            Some(Command::Report(args)) => assert_eq!(args.scan.scan_options().threads, 4),
+            _ => panic!("Expected report subcommand"),
+        }
+        assert_eq!(Cli::parse_from(["node-size", "--threads", "2"]).scan.scan_options().threads, 2);
+        assert!(Cli::parse_from(["node-size"]).scan.scan_options().threads >= 1);
+        assert!(Cli::try_parse_from(["node-size", "-j", "0"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_no_cache() {
+        let cli = Cli::parse_from(["node-size", "--no-cache"]);
+        assert!(cli.scan.no_cache);
+        assert!(cli.scan.scan_options().cache_dir.is_none());
+
+        let cli = Cli::parse_from(["node-size", "report"]);
+        match cli.command {
+            Some(Command::Report(args)) => assert!(!args.scan.no_cache),
+            _ => panic!("Expected report subcommand"),
+        }
+    }
+
+    #[test]
+    fn test_parse_check_budgets() {
+        let cli = Cli::parse_from([
+            "node-size", "check", "app", "--max-total", "500MB", "--max-package", "lodash=1MB",
+            "--max-package", "@babel/core=2MB",
+        ]);
+        match cli.command {
+            Some(Command::Check(args)) => {
+                assert_eq!(args.max_total, Some(500 * 1024 * 1024));
+                assert_eq!(args.max_any, None);
+                assert_eq!(args.max_package.len(), 2);
+                assert_eq!(args.max_package[1], ("@babel/core".to_string(), 2 * 1024 * 1024));
+            }
+            _ => panic!("Expected check subcommand"),
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::lockfile::PackageGraph;
+use crate::scanner::ModuleInfo;
+
+/// A package that is installed more than once, in different versions or in
+/// several locations.
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicateSet {
+    pub name: String,
+    /// Installed copies, largest first
+    pub copies: Vec<InstalledCopy>,
+    pub total_size: u64,
+    /// What deduping down to a single copy would save: everything but the
+    /// largest copy
+    pub wasted: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct InstalledCopy {
+    pub path: String,
+    pub version: Option<String>,
+    pub size: u64,
+    /// Packages whose dependency this copy satisfies, as `name@version`
+    pub dependents: Vec<String>,
+}
+
+impl DuplicateSet {
+    /// Distinct versions of the copies, e.g. `4.17.21, 3.10.1`.
+    pub fn versions(&self) -> String {
+        let mut versions: Vec<&str> = Vec::new();
+        for copy in &self.copies {
+            let version = copy.version.as_deref().unwrap_or("?");
+            if !versions.contains(&version) {
+                versions.push(version);
+            }
+        }
+        versions.join(", ")
+    }
+}
+
+/// Finds every package name installed more than once, most wasted bytes first.
+/// The dependents come from the dependency graph; without one, a nested copy
+/// is attributed to the package whose `node_modules` it lives in.
+pub fn find_duplicates(modules: &[ModuleInfo], graph: Option<&PackageGraph>) -> Vec<DuplicateSet> {
+    let dependents = graph.map(|graph| {
+        let mut dependents: Vec<Vec<String>> = vec![Vec::new(); graph.nodes.len()];
+        for (from, node) in graph.nodes.iter().enumerate() {
+            let label = graph.label(from);
+            for edge in &node.dependencies {
+                if !dependents[edge.to].contains(&label) {
+                    dependents[edge.to].push(label.clone());
+                }
+            }
+        }
+        dependents
+    });
+
+    let mut by_name: BTreeMap<&str, Vec<&ModuleInfo>> = BTreeMap::new();
+    // Nameless and dot entries are no packages, whatever listed them
+    for module in modules.iter().filter(|m| !m.name.is_empty() && !m.name.starts_with('.')) {
+        by_name.entry(&module.name).or_default().push(module);
+    }
+
+    let mut sets: Vec<DuplicateSet> = by_name
+        .into_iter()
+        .filter(|(_, copies)| copies.len() > 1)
+        .map(|(name, copies)| {
+            let mut copies: Vec<InstalledCopy> = copies
+                .into_iter()
+                .map(|module| {
+                    let dependents = match (graph, &dependents) {
+                        (Some(graph), Some(dependents)) => graph
+                            .find_module(module)
+                            .map(|index| dependents[index].clone())
+                            .unwrap_or_default(),
+                        _ => module.parent_name().map(|p| vec![p.to_string()]).unwrap_or_default(),
+                    };
+                    InstalledCopy {
+                        path: module.path.clone(),
+                        version: module.version.clone(),
+                        size: module.size,
+                        dependents,
+                    }
+                })
+                .collect();
+            copies.sort_by_key(|c| std::cmp::Reverse(c.size));
+            let total_size: u64 = copies.iter().map(|c| c.size).sum();
+            DuplicateSet {
+                name: name.to_string(),
+                wasted: total_size - copies[0].size,
+                total_size,
+                copies,
+            }
+        })
+        .collect();
+    sets.sort_by_key(|s| std::cmp::Reverse(s.wasted));
+    sets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn module(path: &str, version: &str, size: u64) -> ModuleInfo {
+        let parent = path.rfind("/node_modules/").map(|idx| path[..idx].to_string());
+        ModuleInfo {
+            name: crate::scanner::package_name_from_path(path).to_string(),
+            size,
+            path: path.to_string(),
+            parent,
+            version: Some(version.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_find_duplicates() {
+        let modules = vec![
+            module("node_modules/lodash", "4.17.21", 500),
+            module("node_modules/a/node_modules/lodash", "3.10.1", 300),
+            module("node_modules/b/node_modules/lodash", "3.10.1", 300),
+            module("node_modules/a", "1.0.0", 10),
+            module("node_modules/b", "1.0.0", 10),
+            module("node_modules/debug", "4.0.0", 50),
+            module("node_modules/a/node_modules/debug", "2.0.0", 60),
+        ];
+
+        let sets = find_duplicates(&modules, None);
+        assert_eq!(sets.len(), 2);
+        assert_eq!(sets[0].name, "lodash");
+        assert_eq!(sets[0].total_size, 1100);
+        assert_eq!(sets[0].wasted, 600);
+        assert_eq!(sets[0].versions(), "4.17.21, 3.10.1");
+        assert_eq!(sets[0].copies[1].dependents, vec!["a"]);
+        assert!(sets[0].copies[0].dependents.is_empty());
+
+        // The largest copy is the one kept, even when it is not the top-level one
+        assert_eq!(sets[1].name, "debug");
+        assert_eq!(sets[1].copies[0].path, "node_modules/a/node_modules/debug");
+        assert_eq!(sets[1].wasted, 50);
+    }
+
+    #[test]
+    fn test_bin_directories_are_not_duplicates() -> std::io::Result<()> {
+        use crate::scanner::{scan_modules_dir, ScanOptions};
+        use std::fs;
+
+        let temp_dir = tempfile::tempdir()?;
+        let node_modules = temp_dir.path();
+        for dir in [".bin", "a/node_modules/.bin", "a/node_modules/b", "b"] {
+            fs::create_dir_all(node_modules.join(dir))?;
+        }
+        let mut modules = scan_modules_dir(node_modules, &ScanOptions::default())?.modules;
+        let sets = find_duplicates(&modules, None);
+        assert_eq!(sets.iter().map(|s| s.name.as_str()).collect::<Vec<_>>(), ["b"]);
+
+        modules.push(module("node_modules/.bin", "", 0));
+        modules.push(module("node_modules/a/node_modules/.bin", "", 0));
+        assert_eq!(find_duplicates(&modules, None).len(), 1);
+        Ok(())
+    }
+}
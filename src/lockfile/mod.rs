@@ -0,0 +1,822 @@
+//! Dependency graphs built from lockfiles.
+//!
+//! Every supported lockfile format is turned into the same [`PackageGraph`]:
+//! one node per installed package plus a root node for the project itself,
+//! with an edge for every dependency that resolved to an installed package.
+
+mod npm;
+mod pnpm;
+mod yarn;
+
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::cli::SizeMetric;
+use crate::scanner::ModuleInfo;
+
+/// How a package depends on another one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DependencyKind {
+    Prod,
+    Dev,
+    Optional,
+    Peer,
+}
+
+impl DependencyKind {
+    pub const ALL: [DependencyKind; 4] = [
+        DependencyKind::Prod,
+        DependencyKind::Dev,
+        DependencyKind::Optional,
+        DependencyKind::Peer,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            DependencyKind::Prod => "prod",
+            DependencyKind::Dev => "dev",
+            DependencyKind::Optional => "optional",
+            DependencyKind::Peer => "peer",
+        }
+    }
+
+    /// How much a package of this kind is needed at runtime, lower is stronger.
+    fn rank(self) -> u8 {
+        match self {
+            DependencyKind::Prod => 0,
+            DependencyKind::Optional => 1,
+            DependencyKind::Peer => 2,
+            DependencyKind::Dev => 3,
+        }
+    }
+
+    /// Kind of a package reached from a package of this kind through an edge
+    /// of kind `edge`: a chain is only as strong as its weakest link.
+    fn through(self, edge: DependencyKind) -> DependencyKind {
+        if edge.rank() > self.rank() {
+            edge
+        } else {
+            self
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Edge {
+    pub to: usize,
+    pub kind: DependencyKind,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PackageNode {
+    pub name: String,
+    pub version: Option<String>,
+    /// Install path relative to the project root, `""` for the root itself
+    pub path: Option<String>,
+    pub dependencies: Vec<Edge>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockfileKind {
+    Npm { version: u32 },
+    YarnClassic,
+    YarnBerry { version: u32 },
+    Pnpm { version: u32 },
+    /// No lockfile, the graph was built from the installed package.json files
+    Installed,
+}
+
+impl fmt::Display for LockfileKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LockfileKind::Npm { version } => write!(f, "package-lock.json v{}", version),
+            LockfileKind::YarnClassic => write!(f, "yarn.lock v1"),
+            LockfileKind::YarnBerry { version } => write!(f, "yarn.lock (Berry, v{})", version),
+            LockfileKind::Pnpm { version } => write!(f, "pnpm-lock.yaml v{}", version),
+            LockfileKind::Installed => write!(f, "installed package.json files"),
+        }
+    }
+}
+
+/// Sizes attributed to one of the project's direct dependencies.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RetainedSize {
+    /// Size of the package itself
+    pub own: u64,
+    /// Size of the package and everything it depends on, directly or not
+    pub closure: u64,
+    /// Part of the closure that no other direct dependency needs, i.e. what
+    /// removing the dependency from package.json would free up
+    pub exclusive: u64,
+}
+
+/// Installed packages and the dependency edges between them. Node 0 is the
+/// project root.
+#[derive(Debug, Clone)]
+pub struct PackageGraph {
+    pub kind: LockfileKind,
+    pub nodes: Vec<PackageNode>,
+    by_path: HashMap<String, usize>,
+    by_name_version: HashMap<(String, String), usize>,
+}
+
+pub const ROOT: usize = 0;
+
+impl PackageGraph {
+    fn new(kind: LockfileKind, root: PackageNode) -> Self {
+        let mut graph = PackageGraph {
+            kind,
+            nodes: Vec::new(),
+            by_path: HashMap::new(),
+            by_name_version: HashMap::new(),
+        };
+        graph.add_node(root);
+        graph
+    }
+
+    fn add_node(&mut self, node: PackageNode) -> usize {
+        let index = self.nodes.len();
+        if let Some(path) = &node.path {
+            self.by_path.insert(path.clone(), index);
+        }
+        if let Some(version) = &node.version {
+            self.by_name_version
+                .entry((node.name.clone(), version.clone()))
+                .or_insert(index);
+        }
+        self.nodes.push(node);
+        index
+    }
+
+    /// Makes `path`, where a link is installed, find the node of the package
+    /// it links to.
+    fn add_link(&mut self, path: &str, target: usize) {
+        self.by_path.insert(path.to_string(), target);
+    }
+
+    pub fn root(&self) -> &PackageNode {
+        &self.nodes[ROOT]
+    }
+
+    pub fn find_by_path(&self, path: &str) -> Option<usize> {
+        self.by_path.get(path).copied()
+    }
+
+    pub fn find_by_name_version(&self, name: &str, version: &str) -> Option<usize> {
+        self.by_name_version
+            .get(&(name.to_string(), version.to_string()))
+            .copied()
+    }
+
+    /// Finds the node for a scanned module, by install path where the lockfile
+    /// records one and by name and version otherwise.
+    pub fn find_module(&self, module: &ModuleInfo) -> Option<usize> {
+        self.find_by_path(&module.path).or_else(|| {
+            let version = module.version.as_deref()?;
+            self.find_by_name_version(&module.name, version)
+        })
+    }
+
+    /// Classifies every node by the strongest chain of dependencies leading to
+    /// it from the project root. A package only needed through a dev
+    /// dependency is dev even when the lockfile says otherwise, and nodes the
+    /// root does not reach at all are `None`.
+    pub fn classify(&self) -> Vec<Option<DependencyKind>> {
+        let mut kinds: Vec<Option<DependencyKind>> = vec![None; self.nodes.len()];
+        let mut queue: VecDeque<(usize, DependencyKind)> =
+            self.root().dependencies.iter().map(|e| (e.to, e.kind)).collect();
+        // A node is revisited only when it is reached through a stronger
+        // chain, which can happen at most once per kind
+        while let Some((index, kind)) = queue.pop_front() {
+            if index == ROOT || kinds[index].is_some_and(|k| k.rank() <= kind.rank()) {
+                continue;
+            }
+            kinds[index] = Some(kind);
+            for edge in &self.nodes[index].dependencies {
+                queue.push_back((edge.to, kind.through(edge.kind)));
+            }
+        }
+        kinds
+    }
+
+    /// Replaces the declared dependency counts of scanned modules with the
+    /// number of dependencies that are actually installed, classifies them as
+    /// prod, dev, optional or peer and fills in what the lockfile knows but
+    /// the package itself did not tell.
+    pub fn annotate(&self, modules: &mut [ModuleInfo]) {
+        let kinds = self.classify();
+        for module in modules {
+            if let Some(index) = self.find_module(module) {
+                let node = &self.nodes[index];
+                module.dependency_count = Some(node.dependencies.len());
+                module.dependency_kind = kinds[index];
+                module.is_dev_dependency = kinds[index] == Some(DependencyKind::Dev);
+                if module.version.is_none() {
+                    module.version = node.version.clone();
+                }
+            }
+        }
+    }
+
+    /// Marks every node reachable from the `start` nodes, themselves included.
+    fn reachable(&self, start: impl IntoIterator<Item = usize>) -> Vec<bool> {
+        let mut seen = vec![false; self.nodes.len()];
+        let mut queue: VecDeque<usize> = start.into_iter().collect();
+        while let Some(index) = queue.pop_front() {
+            if seen[index] {
+                continue;
+            }
+            seen[index] = true;
+            queue.extend(self.nodes[index].dependencies.iter().map(|e| e.to));
+        }
+        seen
+    }
+
+    /// Own, closure and exclusive size of every direct dependency of the
+    /// project, keyed by node. Packages several direct dependencies share
+    /// count towards the closure of each but the exclusive size of none.
+    pub fn retained_sizes(&self, modules: &[ModuleInfo], metric: SizeMetric) -> HashMap<usize, RetainedSize> {
+        let mut sizes = vec![0; self.nodes.len()];
+        for module in modules {
+            if let Some(index) = self.find_module(module) {
+                sizes[index] += module.size_by(metric);
+            }
+        }
+
+        let mut direct: Vec<usize> = self.root().dependencies.iter().map(|e| e.to).collect();
+        direct.sort_unstable();
+        direct.dedup();
+        direct
+            .iter()
+            .map(|&dependency| {
+                let closure = self.reachable([dependency]);
+                let others = self.reachable(direct.iter().copied().filter(|&d| d != dependency));
+                let mut retained = RetainedSize {
+                    own: sizes[dependency],
+                    ..Default::default()
+                };
+                for index in (0..self.nodes.len()).filter(|&i| closure[i]) {
+                    retained.closure += sizes[index];
+                    if !others[index] {
+                        retained.exclusive += sizes[index];
+                    }
+                }
+                (dependency, retained)
+            })
+            .collect()
+    }
+
+    /// Nodes of an installed package, `spec` being a name or `name@version`.
+    pub fn find_package(&self, spec: &str) -> Vec<usize> {
+        // The version separator is the last `@` that does not start a scope
+        let (name, version) = match spec.rfind('@') {
+            Some(at) if at > 0 => (&spec[..at], Some(&spec[at + 1..])),
+            _ => (spec, None),
+        };
+        (ROOT + 1..self.nodes.len())
+            .filter(|&i| {
+                let node = &self.nodes[i];
+                node.name == name && (version.is_none() || node.version.as_deref() == version)
+            })
+            .collect()
+    }
+
+    /// Dependency chains from the project root to `target`, shortest first and
+    /// at most `limit` of them. Each chain is the list of edges followed, so
+    /// the first one starts at the root and the last one ends at `target`.
+    pub fn chains_to(&self, target: usize, limit: usize) -> Vec<Vec<Edge>> {
+        // Distance from the root decides which dependents are tried first, so
+        // the first chain found is a shortest one
+        let mut distance = vec![usize::MAX; self.nodes.len()];
+        distance[ROOT] = 0;
+        let mut queue = VecDeque::from([ROOT]);
+        while let Some(index) = queue.pop_front() {
+            for edge in &self.nodes[index].dependencies {
+                if distance[edge.to] == usize::MAX {
+                    distance[edge.to] = distance[index] + 1;
+                    queue.push_back(edge.to);
+                }
+            }
+        }
+
+        let mut dependents: Vec<Vec<(usize, DependencyKind)>> = vec![Vec::new(); self.nodes.len()];
+        for (from, node) in self.nodes.iter().enumerate() {
+            for edge in &node.dependencies {
+                if distance[from] != usize::MAX {
+                    dependents[edge.to].push((from, edge.kind));
+                }
+            }
+        }
+        for list in &mut dependents {
+            list.sort_by_key(|&(from, _)| distance[from]);
+        }
+
+        let mut chains = Vec::new();
+        let mut path = Vec::new();
+        self.collect_chains(target, &dependents, &mut path, &mut chains, limit);
+        chains.sort_by_key(|chain| chain.len());
+        chains
+    }
+
+    /// Walks up from `node` through its dependents, `path` holding the edges
+    /// from `node` down to the target in reverse order.
+    fn collect_chains(
+        &self,
+        node: usize,
+        dependents: &[Vec<(usize, DependencyKind)>],
+        path: &mut Vec<Edge>,
+        chains: &mut Vec<Vec<Edge>>,
+        limit: usize,
+    ) {
+        if node == ROOT {
+            chains.push(path.iter().rev().copied().collect());
+            return;
+        }
+        for &(from, kind) in &dependents[node] {
+            if chains.len() >= limit {
+                return;
+            }
+            // Packages depending on each other in a cycle are no way in
+            if from != ROOT && (from == node || path.iter().any(|e| e.to == from)) {
+                continue;
+            }
+            path.push(Edge { to: node, kind });
+            self.collect_chains(from, dependents, path, chains, limit);
+            path.pop();
+        }
+    }
+
+    /// `name@version` of a node, the root falling back to `project`.
+    pub fn label(&self, index: usize) -> String {
+        let node = &self.nodes[index];
+        let name = if index == ROOT && node.name.is_empty() { "project" } else { &node.name };
+        match &node.version {
+            Some(version) => format!("{}@{}", name, version),
+            None => name.to_string(),
+        }
+    }
+
+    /// Renders a chain as `app@1.0.0 > jest@29.7.0 (dev) > lodash@4.17.21`,
+    /// marking every step that is not a regular dependency.
+    pub fn describe_chain(&self, chain: &[Edge]) -> String {
+        let mut text = self.label(ROOT);
+        for edge in chain {
+            text.push_str(" > ");
+            text.push_str(&self.label(edge.to));
+            if edge.kind != DependencyKind::Prod {
+                text.push_str(&format!(" ({})", edge.kind.label()));
+            }
+        }
+        text
+    }
+
+    /// Lists the chains leading to `target`, at most `limit` of them, and
+    /// says so when some were left out.
+    pub fn write_chains(&self, target: usize, limit: usize, out: &mut impl io::Write) -> io::Result<()> {
+        // One chain more than shown tells whether there were more
+        let mut chains = self.chains_to(target, limit + 1);
+        if chains.is_empty() {
+            writeln!(out, "  nothing in the project depends on it")?;
+        }
+        let truncated = chains.len() > limit;
+        chains.truncate(limit);
+        for chain in &chains {
+            writeln!(out, "  {}", self.describe_chain(chain))?;
+        }
+        if truncated {
+            writeln!(out, "  (showing the first {} chains, see --limit)", limit)?;
+        }
+        Ok(())
+    }
+
+    /// One line description, e.g. `package-lock.json v3 for app@1.0.0: 120 packages (30 dev, 2 optional, 1 peer)`.
+    /// The kinds are classified from the graph like the modules are, as not
+    /// every lockfile records them.
+    pub fn summary(&self) -> String {
+        let kinds = self.classify();
+        let count = |kind| kinds.iter().filter(|&&k| k == Some(kind)).count();
+        let root = self.root();
+        let project = match &root.version {
+            Some(version) => format!("{}@{}", root.name, version),
+            None => root.name.clone(),
+        };
+        format!(
+            "{} for {}: {} packages ({} dev, {} optional, {} peer)",
+            self.kind,
+            if project.is_empty() { "project" } else { &project },
+            self.nodes.len() - 1,
+            count(DependencyKind::Dev),
+            count(DependencyKind::Optional),
+            count(DependencyKind::Peer),
+        )
+    }
+}
+
+/// Looks for a supported lockfile in the project directory and builds its graph.
+/// Returns `Ok(None)` when the project has no lockfile.
+pub fn load(project_dir: &Path) -> io::Result<Option<PackageGraph>> {
+    let Some(path) = find(project_dir) else {
+        return Ok(None);
+    };
+    let root_manifest = read_root_manifest(project_dir);
+    let content = fs::read_to_string(&path)?;
+    let graph = match path.file_name().and_then(|name| name.to_str()) {
+        Some("yarn.lock") => yarn::parse(&content, root_manifest.as_ref()),
+        Some("pnpm-lock.yaml") => pnpm::parse(&content, root_manifest.as_ref()),
+        _ => npm::parse(&content, root_manifest.as_ref()).map_err(|err| err.to_string()),
+    };
+    graph.map(Some).map_err(|err| invalid_lockfile(&path, err))
+}
+
+/// Finds the lockfile describing the project's `node_modules`.
+pub fn find(project_dir: &Path) -> Option<PathBuf> {
+    // npm-shrinkwrap.json takes precedence over package-lock.json, the hidden
+    // lockfile in node_modules is the last resort
+    [
+        "npm-shrinkwrap.json",
+        "package-lock.json",
+        "yarn.lock",
+        "pnpm-lock.yaml",
+        "node_modules/.package-lock.json",
+    ]
+    .iter()
+    .map(|candidate| project_dir.join(candidate))
+    .find(|path| path.is_file())
+}
+
+/// Builds the graph of a project without a lockfile from the package.json
+/// files of its installed packages, resolving dependencies the way Node does.
+/// Returns `None` when the project has no package.json either.
+pub fn from_installed(project_dir: &Path, modules: &[ModuleInfo]) -> Option<PackageGraph> {
+    let root_manifest = read_root_manifest(project_dir)?;
+    let mut graph = PackageGraph::new(
+        LockfileKind::Installed,
+        PackageNode {
+            name: root_manifest.name.clone().unwrap_or_default(),
+            version: root_manifest.version.clone(),
+            path: Some(String::new()),
+            ..Default::default()
+        },
+    );
+    let mut manifests = vec![root_manifest];
+    for module in modules {
+        graph.add_node(PackageNode {
+            name: module.name.clone(),
+            version: module.version.clone(),
+            path: Some(module.path.clone()),
+            ..Default::default()
+        });
+        manifests.push(read_root_manifest(&project_dir.join(&module.path)).unwrap_or_default());
+    }
+
+    for (index, manifest) in manifests.iter().enumerate() {
+        let from = graph.nodes[index].path.clone().unwrap_or_default();
+        // optionalDependencies win over dependencies listing the same package,
+        // and only the project's own devDependencies get installed
+        let mut sections = vec![
+            (&manifest.optional_dependencies, DependencyKind::Optional),
+            (&manifest.dependencies, DependencyKind::Prod),
+            (&manifest.peer_dependencies, DependencyKind::Peer),
+        ];
+        if index == ROOT {
+            sections.push((&manifest.dev_dependencies, DependencyKind::Dev));
+        }
+        let mut edges: Vec<Edge> = Vec::new();
+        for (dependencies, kind) in sections {
+            let mut names: Vec<&String> = dependencies.keys().collect();
+            names.sort();
+            for name in names {
+                let to = resolve_install_path(&from, name, |p| graph.by_path.contains_key(p))
+                    .and_then(|path| graph.find_by_path(&path));
+                if let Some(to) = to {
+                    if !edges.iter().any(|e| e.to == to) {
+                        edges.push(Edge { to, kind });
+                    }
+                }
+            }
+        }
+        graph.nodes[index].dependencies = edges;
+    }
+    Some(graph)
+}
+
+fn invalid_lockfile(path: &Path, err: impl fmt::Display) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("invalid lockfile {}: {}", path.display(), err),
+    )
+}
+
+/// Dependency sections of the project's own package.json.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RootManifest {
+    pub name: Option<String>,
+    pub version: Option<String>,
+    #[serde(default)]
+    pub dependencies: HashMap<String, String>,
+    #[serde(default)]
+    pub dev_dependencies: HashMap<String, String>,
+    #[serde(default)]
+    pub optional_dependencies: HashMap<String, String>,
+    #[serde(default)]
+    pub peer_dependencies: HashMap<String, String>,
+}
+
+pub fn read_root_manifest(project_dir: &Path) -> Option<RootManifest> {
+    let content = fs::read_to_string(project_dir.join("package.json")).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+/// Install path of the directory whose `node_modules` contains `path`, `""`
+/// for top-level packages and workspaces.
+fn parent_install_path(path: &str) -> Option<&str> {
+    if path.is_empty() {
+        return None;
+    }
+    match path.rfind("node_modules/") {
+        Some(idx) => Some(path[..idx].trim_end_matches('/')),
+        None => Some(""),
+    }
+}
+
+/// Resolves `name` from the package installed at `from` the way Node does:
+/// the closest `node_modules/<name>` walking up towards the project root.
+fn resolve_install_path(from: &str, name: &str, exists: impl Fn(&str) -> bool) -> Option<String> {
+    let mut base = Some(from);
+    while let Some(dir) = base {
+        let candidate = if dir.is_empty() {
+            format!("node_modules/{}", name)
+        } else {
+            format!("{}/node_modules/{}", dir, name)
+        };
+        if exists(&candidate) {
+            return Some(candidate);
+        }
+        base = parent_install_path(dir);
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_install_path() {
+        let installed = ["node_modules/a", "node_modules/b", "node_modules/a/node_modules/b", "node_modules/@s/c"];
+        let exists = |p: &str| installed.contains(&p);
+
+        assert_eq!(resolve_install_path("", "a", exists).as_deref(), Some("node_modules/a"));
+        assert_eq!(
+            resolve_install_path("node_modules/a", "b", exists).as_deref(),
+            Some("node_modules/a/node_modules/b")
+        );
+        assert_eq!(
+            resolve_install_path("node_modules/a/node_modules/b", "@s/c", exists).as_deref(),
+            Some("node_modules/@s/c")
+        );
+        assert_eq!(resolve_install_path("packages/web", "b", exists).as_deref(), Some("node_modules/b"));
+        assert_eq!(resolve_install_path("node_modules/b", "missing", exists), None);
+    }
+
+    #[test]
+    fn test_load_and_annotate() -> io::Result<()> {
+        use crate::scanner::{scan_modules_dir, ScanOptions};
+        use std::io::Write;
+
+        let temp_dir = tempfile::tempdir()?;
+        let project = temp_dir.path();
+        for name in ["a", "b"] {
+            fs::create_dir_all(project.join("node_modules").join(name))?;
+        }
+        fs::File::create(project.join("node_modules/a/package.json"))?
+            .write_all(br#"{"version": "1.0.0", "dependencies": {"b": "*", "not-installed": "*"}, "devDependencies": {"x": "*"}}"#)?;
+        fs::File::create(project.join("package-lock.json"))?.write_all(
+            br#"{
+                "lockfileVersion": 2,
+                "packages": {
+                    "": {"devDependencies": {"a": "*"}},
+                    "node_modules/a": {"version": "1.0.0", "dependencies": {"b": "*", "not-installed": "*"}},
+                    "node_modules/b": {"version": "3.1.0", "dev": true}
+                }
+            }"#,
+        )?;
+
+        let mut modules = scan_modules_dir(&project.join("node_modules"), &ScanOptions::default())?.modules;
+        let a = modules.iter().position(|m| m.name == "a").unwrap();
+        assert_eq!(modules[a].dependency_count, Some(3));
+
+        let graph = load(project)?.expect("lockfile should be found");
+        graph.annotate(&mut modules);
+
+        let b = modules.iter().position(|m| m.name == "b").unwrap();
+        assert_eq!(modules[a].dependency_count, Some(1));
+        assert_eq!(modules[b].version.as_deref(), Some("3.1.0"));
+        assert!(modules[a].is_dev_dependency);
+        assert_eq!(modules[b].dependency_kind, Some(DependencyKind::Dev));
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_annotate_workspace_link() -> io::Result<()> {
+        use crate::scanner::{scan_modules_dir, ScanOptions};
+        use std::io::Write;
+
+        let temp_dir = tempfile::tempdir()?;
+        let project = temp_dir.path();
+        fs::create_dir_all(project.join("packages/web"))?;
+        fs::create_dir_all(project.join("node_modules/left-pad"))?;
+        fs::File::create(project.join("packages/web/package.json"))?
+            .write_all(br#"{"name": "web", "version": "0.0.1", "dependencies": {"left-pad": "*"}}"#)?;
+        std::os::unix::fs::symlink(project.join("packages/web"), project.join("node_modules/web"))?;
+        fs::File::create(project.join("package-lock.json"))?.write_all(
+            br#"{
+                "lockfileVersion": 3,
+                "packages": {
+                    "": {"dependencies": {"web": "*"}},
+                    "node_modules/left-pad": {"version": "1.3.0"},
+                    "node_modules/web": {"resolved": "packages/web", "link": true},
+                    "packages/web": {"name": "web", "version": "0.0.1", "dependencies": {"left-pad": "*"}}
+                }
+            }"#,
+        )?;
+
+        let mut modules = scan_modules_dir(&project.join("node_modules"), &ScanOptions::default())?.modules;
+        let graph = load(project)?.expect("lockfile should be found");
+        graph.annotate(&mut modules);
+
+        // The link is the workspace package, not a package without dependencies
+        let web = modules.iter().find(|m| m.name == "web").unwrap();
+        assert!(web.link_target.is_some());
+        assert_eq!(web.dependency_kind, Some(DependencyKind::Prod));
+        assert_eq!(web.dependency_count, Some(1));
+        let left_pad = modules.iter().find(|m| m.name == "left-pad").unwrap();
+        assert_eq!(left_pad.dependency_kind, Some(DependencyKind::Prod));
+        Ok(())
+    }
+
+    #[test]
+    fn test_classify() {
+        let mut graph = PackageGraph::new(LockfileKind::Installed, PackageNode::default());
+        for name in ["a", "b", "c", "jest", "d", "e", "unused"] {
+            graph.add_node(PackageNode {
+                name: name.to_string(),
+                ..Default::default()
+            });
+        }
+        let edge = |to, kind| Edge { to, kind };
+        // root -> a -> b, root -> c (optional) -> b, root -> jest (dev) -> d,
+        // jest -> a, a -> e (peer)
+        graph.nodes[ROOT].dependencies = vec![
+            edge(1, DependencyKind::Prod),
+            edge(3, DependencyKind::Optional),
+            edge(4, DependencyKind::Dev),
+        ];
+        graph.nodes[1].dependencies = vec![edge(2, DependencyKind::Prod), edge(6, DependencyKind::Peer)];
+        graph.nodes[3].dependencies = vec![edge(2, DependencyKind::Prod)];
+        graph.nodes[4].dependencies = vec![edge(5, DependencyKind::Prod), edge(1, DependencyKind::Prod)];
+
+        let kinds = graph.classify();
+        assert_eq!(
+            kinds,
+            vec![
+                None,
+                Some(DependencyKind::Prod),
+                Some(DependencyKind::Prod),
+                Some(DependencyKind::Optional),
+                Some(DependencyKind::Dev),
+                Some(DependencyKind::Dev),
+                Some(DependencyKind::Peer),
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_chains_to() {
+        let mut graph = PackageGraph::new(
+            LockfileKind::Installed,
+            PackageNode {
+                name: "app".to_string(),
+                ..Default::default()
+            },
+        );
+        for name in ["a", "b", "jest", "lodash", "@s/x"] {
+            graph.add_node(PackageNode {
+                name: name.to_string(),
+                version: Some("1.0.0".to_string()),
+                ..Default::default()
+            });
+        }
+        let edge = |to, kind| Edge { to, kind };
+        // app -> a -> b -> lodash, app -> jest (dev) -> lodash, with a cycle
+        // between a and b
+        graph.nodes[ROOT].dependencies = vec![edge(1, DependencyKind::Prod), edge(3, DependencyKind::Dev)];
+        graph.nodes[1].dependencies = vec![edge(2, DependencyKind::Prod)];
+        graph.nodes[2].dependencies = vec![edge(1, DependencyKind::Prod), edge(4, DependencyKind::Prod)];
+        graph.nodes[3].dependencies = vec![edge(4, DependencyKind::Prod)];
+
+        assert_eq!(graph.find_package("lodash"), vec![4]);
+        assert_eq!(graph.find_package("@s/x@1.0.0"), vec![5]);
+        assert!(graph.find_package("lodash@2.0.0").is_empty());
+
+        let chains: Vec<String> = graph.chains_to(4, 10).iter().map(|c| graph.describe_chain(c)).collect();
+        assert_eq!(
+            chains,
+            vec![
+                "app > jest@1.0.0 (dev) > lodash@1.0.0",
+                "app > a@1.0.0 > b@1.0.0 > lodash@1.0.0",
+            ]
+        );
+        assert_eq!(graph.chains_to(4, 1).len(), 1);
+        assert!(graph.chains_to(5, 10).is_empty());
+
+        let written = |target, limit| {
+            let mut out = Vec::new();
+            graph.write_chains(target, limit, &mut out).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        // Exactly as many chains as the limit, nothing was left out
+        assert_eq!(written(4, 2).lines().count(), 2);
+        assert_eq!(
+            written(4, 1),
+            "  app > jest@1.0.0 (dev) > lodash@1.0.0\n  (showing the first 1 chains, see --limit)\n"
+        );
+        assert_eq!(written(5, 10), "  nothing in the project depends on it\n");
+    }
+
+    #[test]
+    fn test_retained_sizes() {
+        let mut graph = PackageGraph::new(LockfileKind::Installed, PackageNode::default());
+        let mut modules = Vec::new();
+        for (name, size) in [("a", 100), ("b", 10), ("shared", 1000), ("only-a", 50)] {
+            graph.add_node(PackageNode {
+                name: name.to_string(),
+                path: Some(format!("node_modules/{}", name)),
+                ..Default::default()
+            });
+            modules.push(ModuleInfo {
+                name: name.to_string(),
+                path: format!("node_modules/{}", name),
+                size,
+                disk_size: 4096,
+                ..Default::default()
+            });
+        }
+        let edge = |to| Edge { to, kind: DependencyKind::Prod };
+        // app -> a -> shared, a -> only-a, app -> b -> shared
+        graph.nodes[ROOT].dependencies = vec![edge(1), edge(2)];
+        graph.nodes[1].dependencies = vec![edge(3), edge(4)];
+        graph.nodes[2].dependencies = vec![edge(3)];
+
+        let retained = graph.retained_sizes(&modules, SizeMetric::Apparent);
+        assert_eq!(retained.len(), 2);
+        assert_eq!(retained[&1], RetainedSize { own: 100, closure: 1150, exclusive: 150 });
+        assert_eq!(retained[&2], RetainedSize { own: 10, closure: 1010, exclusive: 10 });
+
+        let retained = graph.retained_sizes(&modules, SizeMetric::Disk);
+        assert_eq!(retained[&1], RetainedSize { own: 4096, closure: 3 * 4096, exclusive: 2 * 4096 });
+    }
+
+    #[test]
+    fn test_from_installed() -> io::Result<()> {
+        use crate::scanner::{scan_modules_dir, ScanOptions};
+
+        let temp_dir = tempfile::tempdir()?;
+        let project = temp_dir.path();
+        let manifests = [
+            ("package.json", r#"{"name": "app", "dependencies": {"a": "*"}, "devDependencies": {"jest": "*"}}"#),
+            ("node_modules/a/package.json", r#"{"version": "1.0.0", "dependencies": {"b": "*"}}"#),
+            ("node_modules/a/node_modules/b/package.json", r#"{"version": "2.0.0"}"#),
+            ("node_modules/b/package.json", r#"{"version": "1.0.0"}"#),
+            ("node_modules/jest/package.json", r#"{"version": "29.0.0", "dependencies": {"b": "*"}}"#),
+        ];
+        for (path, content) in manifests {
+            let path = project.join(path);
+            fs::create_dir_all(path.parent().unwrap())?;
+            fs::write(path, content)?;
+        }
+
+        let mut modules = scan_modules_dir(&project.join("node_modules"), &ScanOptions::default())?.modules;
+        let graph = from_installed(project, &modules).expect("package.json should be found");
+        graph.annotate(&mut modules);
+
+        let kind = |path: &str| modules.iter().find(|m| m.path == path).unwrap().dependency_kind;
+        assert_eq!(kind("node_modules/a"), Some(DependencyKind::Prod));
+        assert_eq!(kind("node_modules/a/node_modules/b"), Some(DependencyKind::Prod));
+        assert_eq!(kind("node_modules/b"), Some(DependencyKind::Dev));
+        assert_eq!(kind("node_modules/jest"), Some(DependencyKind::Dev));
+        assert_eq!(
+            graph.summary(),
+            "installed package.json files for app: 4 packages (2 dev, 0 optional, 0 peer)"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,312 @@
+//! `package-lock.json` and `npm-shrinkwrap.json`, lockfileVersion 1, 2 and 3.
+//!
+//! Version 2 and 3 list every installed package in a flat `packages` map keyed
+//! by install path. Version 1 only has the nested `dependencies` tree, which is
+//! flattened into the same install paths first.
+
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+
+use super::{resolve_install_path, DependencyKind, Edge, LockfileKind, PackageGraph, PackageNode, RootManifest};
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PackageLock {
+    name: Option<String>,
+    version: Option<String>,
+    #[serde(default = "default_lockfile_version")]
+    lockfile_version: u32,
+    #[serde(default)]
+    packages: BTreeMap<String, LockPackage>,
+    #[serde(default)]
+    dependencies: BTreeMap<String, LockDependency>,
+}
+
+fn default_lockfile_version() -> u32 {
+    1
+}
+
+/// Entry of the `packages` map (v2, v3)
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LockPackage {
+    name: Option<String>,
+    version: Option<String>,
+    resolved: Option<String>,
+    #[serde(default)]
+    link: bool,
+    #[serde(default)]
+    dependencies: BTreeMap<String, String>,
+    #[serde(default)]
+    dev_dependencies: BTreeMap<String, String>,
+    #[serde(default)]
+    optional_dependencies: BTreeMap<String, String>,
+    #[serde(default)]
+    peer_dependencies: BTreeMap<String, String>,
+}
+
+/// Entry of the nested `dependencies` tree (v1)
+#[derive(Debug, Default, Deserialize)]
+struct LockDependency {
+    version: Option<String>,
+    resolved: Option<String>,
+    #[serde(default)]
+    dev: bool,
+    #[serde(default)]
+    optional: bool,
+    #[serde(default)]
+    requires: BTreeMap<String, String>,
+    #[serde(default)]
+    dependencies: BTreeMap<String, LockDependency>,
+}
+
+pub fn parse(content: &str, root_manifest: Option<&RootManifest>) -> Result<PackageGraph, serde_json::Error> {
+    let lock: PackageLock = serde_json::from_str(content)?;
+    let kind = LockfileKind::Npm { version: lock.lockfile_version };
+
+    let packages = if lock.packages.is_empty() {
+        flatten_v1(&lock, root_manifest)
+    } else {
+        lock.packages
+    };
+
+    let mut graph = PackageGraph::new(
+        kind,
+        PackageNode {
+            name: lock
+                .name
+                .or_else(|| root_manifest.and_then(|m| m.name.clone()))
+                .unwrap_or_default(),
+            version: lock.version.or_else(|| root_manifest.and_then(|m| m.version.clone())),
+            path: Some(String::new()),
+            ..Default::default()
+        },
+    );
+
+    for (path, package) in &packages {
+        // Links get no node of their own, they lead to the node of their target
+        if path.is_empty() || package.link {
+            continue;
+        }
+        let name = package
+            .name
+            .clone()
+            .unwrap_or_else(|| crate::scanner::package_name_from_path(path).to_string());
+        graph.add_node(PackageNode {
+            name,
+            version: package.version.clone(),
+            path: Some(path.clone()),
+            dependencies: Vec::new(),
+        });
+    }
+
+    // Links (workspaces, `npm link`) point at the directory holding the real package
+    let links: HashMap<&str, &str> = packages
+        .iter()
+        .filter(|(_, p)| p.link)
+        .filter_map(|(path, p)| Some((path.as_str(), p.resolved.as_deref()?)))
+        .collect();
+    for (&path, &target) in &links {
+        if let Some(target) = graph.find_by_path(target) {
+            graph.add_link(path, target);
+        }
+    }
+
+    for (path, package) in &packages {
+        if package.link {
+            continue;
+        }
+        let Some(from) = graph.find_by_path(path) else {
+            continue;
+        };
+
+        let mut sections = vec![
+            (&package.dependencies, DependencyKind::Prod),
+            (&package.optional_dependencies, DependencyKind::Optional),
+            (&package.peer_dependencies, DependencyKind::Peer),
+        ];
+        // Only the project's own dev dependencies get installed
+        if from == super::ROOT || links.values().any(|target| target == path) {
+            sections.push((&package.dev_dependencies, DependencyKind::Dev));
+        }
+
+        let mut edges: Vec<Edge> = Vec::new();
+        for (section, kind) in sections {
+            for name in section.keys() {
+                let Some(target) = resolve_install_path(path, name, |p| graph.find_by_path(p).is_some()) else {
+                    // Missing optional and peer dependencies are normal
+                    continue;
+                };
+                if let Some(to) = graph.find_by_path(&target) {
+                    if !edges.iter().any(|e| e.to == to) {
+                        edges.push(Edge { to, kind });
+                    }
+                }
+            }
+        }
+        graph.nodes[from].dependencies = edges;
+    }
+
+    Ok(graph)
+}
+
+/// Turns the v1 tree into the v2 `packages` layout. v1 has no entry for the
+/// project itself, so root dependencies come from package.json, or from the
+/// top-level entries when there is no package.json.
+fn flatten_v1(lock: &PackageLock, root_manifest: Option<&RootManifest>) -> BTreeMap<String, LockPackage> {
+    fn visit(prefix: &str, deps: &BTreeMap<String, LockDependency>, out: &mut BTreeMap<String, LockPackage>) {
+        for (name, dep) in deps {
+            let path = format!("{}node_modules/{}", prefix, name);
+            visit(&format!("{}/", path), &dep.dependencies, out);
+            out.insert(
+                path,
+                LockPackage {
+                    name: Some(name.clone()),
+                    version: dep.version.clone(),
+                    resolved: dep.resolved.clone(),
+                    dependencies: dep.requires.clone(),
+                    ..Default::default()
+                },
+            );
+        }
+    }
+
+    let mut packages = BTreeMap::new();
+    visit("", &lock.dependencies, &mut packages);
+
+    let mut root = LockPackage::default();
+    match root_manifest {
+        Some(manifest) => {
+            let collect = |section: &HashMap<String, String>| -> BTreeMap<String, String> {
+                section.iter().map(|(k, v)| (k.clone(), v.clone())).collect()
+            };
+            root.dependencies = collect(&manifest.dependencies);
+            root.dev_dependencies = collect(&manifest.dev_dependencies);
+            root.optional_dependencies = collect(&manifest.optional_dependencies);
+            root.peer_dependencies = collect(&manifest.peer_dependencies);
+        }
+        None => {
+            for (name, dep) in &lock.dependencies {
+                let version = dep.version.clone().unwrap_or_default();
+                match (dep.dev, dep.optional) {
+                    (true, _) => root.dev_dependencies.insert(name.clone(), version),
+                    (false, true) => root.optional_dependencies.insert(name.clone(), version),
+                    (false, false) => root.dependencies.insert(name.clone(), version),
+                };
+            }
+        }
+    }
+    packages.insert(String::new(), root);
+    packages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edges(graph: &PackageGraph, path: &str) -> Vec<(String, DependencyKind)> {
+        let node = &graph.nodes[graph.find_by_path(path).unwrap()];
+        node.dependencies
+            .iter()
+            .map(|e| (graph.nodes[e.to].path.clone().unwrap(), e.kind))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_v3() {
+        let content = r#"{
+            "name": "app",
+            "version": "1.0.0",
+            "lockfileVersion": 3,
+            "packages": {
+                "": {
+                    "name": "app",
+                    "dependencies": {"a": "^1.0.0", "web": "*"},
+                    "devDependencies": {"jest": "^29.0.0"}
+                },
+                "node_modules/a": {
+                    "version": "1.2.0",
+                    "resolved": "https://registry.npmjs.org/a/-/a-1.2.0.tgz",
+                    "dependencies": {"b": "^2.0.0", "c": "^1.0.0"},
+                    "peerDependencies": {"missing-peer": "*"}
+                },
+                "node_modules/a/node_modules/b": {"version": "2.0.0"},
+                "node_modules/b": {"version": "1.0.0", "dev": true},
+                "node_modules/c": {"version": "1.0.0", "optional": true},
+                "node_modules/jest": {"version": "29.0.0", "dev": true, "dependencies": {"b": "^1.0.0"}},
+                "node_modules/web": {"resolved": "packages/web", "link": true},
+                "packages/web": {"name": "web", "version": "0.0.1", "devDependencies": {"c": "*"}}
+            }
+        }"#;
+        let graph = parse(content, None).unwrap();
+
+        assert_eq!(graph.kind, LockfileKind::Npm { version: 3 });
+        assert_eq!(graph.root().name, "app");
+        assert_eq!(
+            edges(&graph, ""),
+            vec![
+                ("node_modules/a".to_string(), DependencyKind::Prod),
+                ("packages/web".to_string(), DependencyKind::Prod),
+                ("node_modules/jest".to_string(), DependencyKind::Dev),
+            ]
+        );
+        assert_eq!(
+            edges(&graph, "node_modules/a"),
+            vec![
+                ("node_modules/a/node_modules/b".to_string(), DependencyKind::Prod),
+                ("node_modules/c".to_string(), DependencyKind::Prod),
+            ]
+        );
+        assert_eq!(edges(&graph, "node_modules/jest"), vec![("node_modules/b".to_string(), DependencyKind::Prod)]);
+        assert_eq!(edges(&graph, "packages/web"), vec![("node_modules/c".to_string(), DependencyKind::Dev)]);
+
+        let kinds = graph.classify();
+        assert_eq!(kinds[graph.find_by_path("node_modules/b").unwrap()], Some(DependencyKind::Dev));
+        // c is flagged optional, but a depends on it like on any other package
+        assert_eq!(
+            graph.summary(),
+            "package-lock.json v3 for app@1.0.0: 6 packages (2 dev, 0 optional, 0 peer)"
+        );
+    }
+
+    #[test]
+    fn test_parse_v1() {
+        let content = r#"{
+            "name": "app",
+            "lockfileVersion": 1,
+            "requires": true,
+            "dependencies": {
+                "a": {
+                    "version": "1.2.0",
+                    "requires": {"b": "^2.0.0"},
+                    "dependencies": {
+                        "b": {"version": "2.0.0"}
+                    }
+                },
+                "b": {"version": "1.0.0", "dev": true},
+                "@s/c": {"version": "1.0.0", "optional": true}
+            }
+        }"#;
+        let graph = parse(content, None).unwrap();
+
+        assert_eq!(graph.kind, LockfileKind::Npm { version: 1 });
+        assert_eq!(graph.nodes.len(), 5);
+        assert_eq!(
+            edges(&graph, ""),
+            vec![
+                ("node_modules/a".to_string(), DependencyKind::Prod),
+                ("node_modules/@s/c".to_string(), DependencyKind::Optional),
+                ("node_modules/b".to_string(), DependencyKind::Dev),
+            ]
+        );
+        assert_eq!(
+            edges(&graph, "node_modules/a"),
+            vec![("node_modules/a/node_modules/b".to_string(), DependencyKind::Prod)]
+        );
+
+        // With a package.json the root edges follow the manifest
+        let manifest: RootManifest = serde_json::from_str(r#"{"dependencies": {"b": "^1.0.0"}}"#).unwrap();
+        let graph = parse(content, Some(&manifest)).unwrap();
+        assert_eq!(edges(&graph, ""), vec![("node_modules/b".to_string(), DependencyKind::Prod)]);
+    }
+}
@@ -0,0 +1,349 @@
+//! `pnpm-lock.yaml`, lockfileVersion 5.x, 6.x and 9.x.
+//!
+//! Package keys changed between versions: `/name/1.0.0_peer@1.0.0` in 5.x,
+//! `/name@1.0.0(peer@1.0.0)` in 6.x and `name@1.0.0(peer@1.0.0)` in 9.x, where
+//! the dependency information also moved from `packages` to `snapshots`. All
+//! of them are normalized to `name@version(peers)` here. Like Yarn, pnpm does
+//! not record install paths, so nodes are matched by name and version.
+
+use serde_yaml_ng::Value;
+use std::collections::HashMap;
+
+use super::{DependencyKind, Edge, LockfileKind, PackageGraph, PackageNode, RootManifest, ROOT};
+
+struct Lockfile<'a> {
+    major: u32,
+    document: &'a Value,
+}
+
+impl Lockfile<'_> {
+    /// Turns a `packages` or `snapshots` key into `name@version(peers)`.
+    fn normalize_key(&self, key: &str) -> Option<(String, String)> {
+        let key = key.strip_prefix('/').unwrap_or(key);
+        let (name, version) = if self.major < 6 {
+            key.rsplit_once('/')?
+        } else {
+            let at = key.get(1..)?.find('@')? + 1;
+            (&key[..at], &key[at + 1..])
+        };
+        Some((name.to_string(), version.to_string()))
+    }
+
+    /// Key of the package a dependency reference such as `1.0.0`,
+    /// `1.0.0(react@18.2.0)`, `/other/1.0.0` or `other@1.0.0` points at.
+    fn reference_key(&self, name: &str, reference: &str) -> Option<String> {
+        if reference.starts_with("link:") || reference.starts_with("file:") {
+            return None;
+        }
+        if reference.starts_with('/') {
+            let (name, version) = self.normalize_key(reference)?;
+            return Some(format!("{}@{}", name, version));
+        }
+        // npm aliases in 9.x reference the real package as name@version
+        if !reference.starts_with(|c: char| c.is_ascii_digit()) && reference.get(1..)?.contains('@') {
+            return Some(reference.to_string());
+        }
+        Some(format!("{}@{}", name, reference))
+    }
+}
+
+/// Version without the peer dependency suffix.
+fn plain_version(version: &str, major: u32) -> &str {
+    let end = if major < 6 { version.find('_') } else { version.find('(') };
+    &version[..end.unwrap_or(version.len())]
+}
+
+fn string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+fn entries(value: Option<&Value>) -> Vec<(String, &Value)> {
+    value
+        .and_then(|v| v.as_mapping())
+        .map(|map| map.iter().filter_map(|(k, v)| Some((string(k)?, v))).collect())
+        .unwrap_or_default()
+}
+
+/// A dependency's resolved reference: a plain string up to 5.x, and a
+/// `{specifier, version}` map for importers from 6.x on.
+fn reference(value: &Value) -> Option<String> {
+    match value {
+        Value::Mapping(_) => value.get("version").and_then(string),
+        _ => string(value),
+    }
+}
+
+pub fn parse(content: &str, root_manifest: Option<&RootManifest>) -> Result<PackageGraph, String> {
+    let document: Value = serde_yaml_ng::from_str(content).map_err(|err| err.to_string())?;
+    let version = document
+        .get("lockfileVersion")
+        .and_then(string)
+        .ok_or("missing lockfileVersion")?;
+    let major: u32 = version
+        .split('.')
+        .next()
+        .and_then(|m| m.parse().ok())
+        .ok_or_else(|| format!("unsupported lockfileVersion {}", version))?;
+    let lockfile = Lockfile { major, document: &document };
+
+    // The project's own dependencies live under importers['.'] in workspaces
+    // and from 9.x on, and at the top level of older single-project lockfiles
+    let importer = document
+        .get("importers")
+        .and_then(|importers| importers.get("."))
+        .unwrap_or(&document);
+
+    let mut graph = PackageGraph::new(
+        LockfileKind::Pnpm { version: major },
+        PackageNode {
+            name: root_manifest.and_then(|m| m.name.clone()).unwrap_or_default(),
+            version: root_manifest.and_then(|m| m.version.clone()),
+            path: Some(String::new()),
+            ..Default::default()
+        },
+    );
+
+    // 9.x keeps package metadata in `packages` and the resolved dependencies
+    // of every peer variant in `snapshots`
+    let packages = entries(lockfile.document.get("packages"));
+    let snapshots = if major >= 9 {
+        entries(lockfile.document.get("snapshots"))
+    } else {
+        packages
+    };
+
+    let mut by_key: HashMap<String, usize> = HashMap::new();
+    for (key, _) in &snapshots {
+        let Some((name, version)) = lockfile.normalize_key(key) else {
+            continue;
+        };
+        let node = PackageNode {
+            name: name.clone(),
+            version: Some(plain_version(&version, major).to_string()),
+            path: None,
+            dependencies: Vec::new(),
+        };
+        let index = graph.add_node(node);
+        by_key.insert(format!("{}@{}", name, version), index);
+    }
+
+    let edges_of = |value: &Value, sections: &[(&str, DependencyKind)]| -> Vec<Edge> {
+        let mut edges: Vec<Edge> = Vec::new();
+        for (section, kind) in sections {
+            for (name, dependency) in entries(value.get(*section)) {
+                let Some(key) = reference(dependency).and_then(|r| lockfile.reference_key(&name, &r)) else {
+                    continue;
+                };
+                if let Some(&to) = by_key.get(&key) {
+                    if !edges.iter().any(|e| e.to == to) {
+                        edges.push(Edge { to, kind: *kind });
+                    }
+                }
+            }
+        }
+        edges
+    };
+
+    for (key, snapshot) in &snapshots {
+        let Some((name, version)) = lockfile.normalize_key(key) else {
+            continue;
+        };
+        let index = by_key[&format!("{}@{}", name, version)];
+        graph.nodes[index].dependencies = edges_of(
+            snapshot,
+            &[
+                ("dependencies", DependencyKind::Prod),
+                ("optionalDependencies", DependencyKind::Optional),
+            ],
+        );
+    }
+
+    graph.nodes[ROOT].dependencies = edges_of(
+        importer,
+        &[
+            ("dependencies", DependencyKind::Prod),
+            ("optionalDependencies", DependencyKind::Optional),
+            ("devDependencies", DependencyKind::Dev),
+        ],
+    );
+
+    Ok(graph)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dependencies(graph: &PackageGraph, node: usize) -> Vec<(String, DependencyKind)> {
+        graph.nodes[node]
+            .dependencies
+            .iter()
+            .map(|e| {
+                let to = &graph.nodes[e.to];
+                (format!("{}@{}", to.name, to.version.as_deref().unwrap_or("")), e.kind)
+            })
+            .collect()
+    }
+
+    fn find(graph: &PackageGraph, name: &str, version: &str) -> usize {
+        graph.find_by_name_version(name, version).unwrap()
+    }
+
+    #[test]
+    fn test_parse_v5() {
+        let content = r#"
+lockfileVersion: 5.4
+
+specifiers:
+  '@s/a': ^1.0.0
+  b: ^2.0.0
+
+dependencies:
+  '@s/a': 1.0.0_react@18.2.0
+
+devDependencies:
+  b: 2.0.0
+
+packages:
+
+  /@s/a/1.0.0_react@18.2.0:
+    resolution: {integrity: sha512-abc}
+    peerDependencies:
+      react: '*'
+    dependencies:
+      react: 18.2.0
+    dev: false
+
+  /b/2.0.0:
+    resolution: {integrity: sha512-def}
+    optionalDependencies:
+      react: 18.2.0
+    dev: true
+
+  /react/18.2.0:
+    resolution: {integrity: sha512-ghi}
+    dev: false
+"#;
+        let graph = parse(content, None).unwrap();
+        assert_eq!(graph.kind, LockfileKind::Pnpm { version: 5 });
+        assert_eq!(
+            dependencies(&graph, ROOT),
+            vec![
+                ("@s/a@1.0.0".to_string(), DependencyKind::Prod),
+                ("b@2.0.0".to_string(), DependencyKind::Dev),
+            ]
+        );
+        assert_eq!(
+            dependencies(&graph, find(&graph, "b", "2.0.0")),
+            vec![("react@18.2.0".to_string(), DependencyKind::Optional)]
+        );
+        assert_eq!(graph.classify()[find(&graph, "b", "2.0.0")], Some(DependencyKind::Dev));
+    }
+
+    #[test]
+    fn test_parse_v6() {
+        let content = r#"
+lockfileVersion: '6.0'
+
+importers:
+  .:
+    dependencies:
+      '@s/a':
+        specifier: ^1.0.0
+        version: 1.0.0(react@18.2.0)
+      react:
+        specifier: ^18.0.0
+        version: 18.2.0
+
+packages:
+  /@s/a@1.0.0(react@18.2.0):
+    resolution: {integrity: sha512-abc}
+    dependencies:
+      react: 18.2.0
+      loose-envify: /js-tokens@4.0.0
+    dev: false
+
+  /react@18.2.0:
+    resolution: {integrity: sha512-ghi}
+    dev: false
+
+  /js-tokens@4.0.0:
+    resolution: {integrity: sha512-jkl}
+    dev: false
+"#;
+        let graph = parse(content, None).unwrap();
+        assert_eq!(graph.kind, LockfileKind::Pnpm { version: 6 });
+        assert_eq!(
+            dependencies(&graph, ROOT),
+            vec![
+                ("@s/a@1.0.0".to_string(), DependencyKind::Prod),
+                ("react@18.2.0".to_string(), DependencyKind::Prod),
+            ]
+        );
+        // Aliased dependencies point at the real package
+        assert_eq!(
+            dependencies(&graph, find(&graph, "@s/a", "1.0.0")),
+            vec![
+                ("react@18.2.0".to_string(), DependencyKind::Prod),
+                ("js-tokens@4.0.0".to_string(), DependencyKind::Prod),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_v9() {
+        let content = r#"
+lockfileVersion: '9.0'
+
+settings:
+  autoInstallPeers: true
+
+importers:
+  .:
+    dependencies:
+      a:
+        specifier: ^1.0.0
+        version: 1.0.0
+    devDependencies:
+      typescript:
+        specifier: ^5.0.0
+        version: 5.4.5
+
+packages:
+  a@1.0.0:
+    resolution: {integrity: sha512-abc}
+  b@2.0.0:
+    resolution: {integrity: sha512-def}
+  typescript@5.4.5:
+    resolution: {integrity: sha512-ghi}
+    hasBin: true
+
+snapshots:
+  a@1.0.0:
+    dependencies:
+      b: 2.0.0
+  b@2.0.0: {}
+  typescript@5.4.5: {}
+"#;
+        let graph = parse(content, None).unwrap();
+        assert_eq!(graph.kind, LockfileKind::Pnpm { version: 9 });
+        assert_eq!(graph.nodes.len(), 4);
+        assert_eq!(
+            dependencies(&graph, ROOT),
+            vec![
+                ("a@1.0.0".to_string(), DependencyKind::Prod),
+                ("typescript@5.4.5".to_string(), DependencyKind::Dev),
+            ]
+        );
+        assert_eq!(
+            dependencies(&graph, find(&graph, "a", "1.0.0")),
+            vec![("b@2.0.0".to_string(), DependencyKind::Prod)]
+        );
+        // pnpm 9 records no dev flags, typescript is dev by how it is reached
+        assert!(graph.summary().ends_with(": 3 packages (1 dev, 0 optional, 0 peer)"));
+    }
+}
@@ -0,0 +1,406 @@
+//! `yarn.lock`, both the classic v1 text format and the YAML based format
+//! written by Yarn 2+ (Berry).
+//!
+//! Yarn records which descriptor (`name@range`) resolves to which version, but
+//! not where a package ends up on disk. Nodes therefore carry no install path
+//! and are matched to scanned modules by name and version.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use super::{DependencyKind, Edge, LockfileKind, PackageGraph, PackageNode, RootManifest, ROOT};
+
+#[derive(Debug, Default)]
+struct YarnEntry {
+    descriptors: Vec<String>,
+    version: Option<String>,
+    /// Dependency name, range and kind
+    dependencies: Vec<(String, String, DependencyKind)>,
+    /// Set on the entry for the project itself (Berry only)
+    is_root_workspace: bool,
+}
+
+pub fn parse(content: &str, root_manifest: Option<&RootManifest>) -> Result<PackageGraph, String> {
+    if content.lines().any(|line| line.starts_with("__metadata:")) {
+        let (version, entries) = parse_berry(content)?;
+        Ok(build_graph(LockfileKind::YarnBerry { version }, entries, root_manifest))
+    } else {
+        let entries = parse_classic(content)?;
+        Ok(build_graph(LockfileKind::YarnClassic, entries, root_manifest))
+    }
+}
+
+/// Splits `name@range` into its parts, keeping the `@` of scoped names.
+fn split_descriptor(descriptor: &str) -> Option<(&str, &str)> {
+    let at = descriptor.get(1..)?.find('@')? + 1;
+    Some((&descriptor[..at], &descriptor[at + 1..]))
+}
+
+fn unquote(text: &str) -> &str {
+    let text = text.trim();
+    text.strip_prefix('"')
+        .and_then(|t| t.strip_suffix('"'))
+        .unwrap_or(text)
+}
+
+/// Parses the classic format: unindented descriptor lists, two-space indented
+/// fields and four-space indented dependency lists.
+fn parse_classic(content: &str) -> Result<Vec<YarnEntry>, String> {
+    let mut entries: Vec<YarnEntry> = Vec::new();
+    let mut section: Option<DependencyKind> = None;
+
+    for (number, line) in content.lines().enumerate() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let indent = line.len() - line.trim_start().len();
+
+        if indent == 0 {
+            let header = trimmed
+                .strip_suffix(':')
+                .ok_or_else(|| format!("line {}: expected a descriptor list", number + 1))?;
+            entries.push(YarnEntry {
+                descriptors: header.split(", ").map(|d| unquote(d).to_string()).collect(),
+                ..Default::default()
+            });
+            section = None;
+            continue;
+        }
+
+        let entry = entries
+            .last_mut()
+            .ok_or_else(|| format!("line {}: field outside of an entry", number + 1))?;
+        if indent <= 2 {
+            section = match trimmed {
+                "dependencies:" => Some(DependencyKind::Prod),
+                "optionalDependencies:" => Some(DependencyKind::Optional),
+                "peerDependencies:" => Some(DependencyKind::Peer),
+                _ => {
+                    if let Some((key, value)) = trimmed.split_once(' ') {
+                        if key == "version" {
+                            entry.version = Some(unquote(value).to_string());
+                        }
+                    }
+                    None
+                }
+            };
+        } else if let Some(kind) = section {
+            if let Some((name, range)) = trimmed.split_once(' ') {
+                entry.dependencies.push((unquote(name).to_string(), unquote(range).to_string(), kind));
+            }
+        }
+    }
+
+    Ok(entries)
+}
+
+fn yaml_string(value: &serde_yaml_ng::Value) -> Option<String> {
+    match value {
+        serde_yaml_ng::Value::String(s) => Some(s.clone()),
+        serde_yaml_ng::Value::Number(n) => Some(n.to_string()),
+        serde_yaml_ng::Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+fn yaml_map(value: Option<&serde_yaml_ng::Value>) -> Vec<(String, &serde_yaml_ng::Value)> {
+    value
+        .and_then(|v| v.as_mapping())
+        .map(|map| {
+            map.iter()
+                .filter_map(|(k, v)| Some((yaml_string(k)?, v)))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Parses the Berry format, which is plain YAML.
+fn parse_berry(content: &str) -> Result<(u32, Vec<YarnEntry>), String> {
+    let document: BTreeMap<String, serde_yaml_ng::Value> =
+        serde_yaml_ng::from_str(content).map_err(|err| err.to_string())?;
+
+    let mut version = 0;
+    let mut entries = Vec::new();
+    for (key, value) in &document {
+        if key == "__metadata" {
+            version = value
+                .get("version")
+                .and_then(yaml_string)
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(0);
+            continue;
+        }
+
+        let optional: HashSet<String> = yaml_map(value.get("dependenciesMeta"))
+            .into_iter()
+            .filter(|(_, meta)| meta.get("optional").and_then(|o| o.as_bool()) == Some(true))
+            .map(|(name, _)| name)
+            .collect();
+
+        let mut dependencies = Vec::new();
+        for (name, range) in yaml_map(value.get("dependencies")) {
+            let kind = if optional.contains(&name) {
+                DependencyKind::Optional
+            } else {
+                DependencyKind::Prod
+            };
+            dependencies.push((name, yaml_string(range).unwrap_or_default(), kind));
+        }
+        for (name, range) in yaml_map(value.get("peerDependencies")) {
+            dependencies.push((name, yaml_string(range).unwrap_or_default(), DependencyKind::Peer));
+        }
+
+        let resolution = value.get("resolution").and_then(yaml_string).unwrap_or_default();
+        entries.push(YarnEntry {
+            descriptors: key.split(", ").map(|d| d.trim().to_string()).collect(),
+            version: value.get("version").and_then(yaml_string),
+            dependencies,
+            is_root_workspace: resolution.ends_with("@workspace:."),
+        });
+    }
+
+    Ok((version, entries))
+}
+
+fn build_graph(kind: LockfileKind, entries: Vec<YarnEntry>, root_manifest: Option<&RootManifest>) -> PackageGraph {
+    let root_entry = entries.iter().position(|e| e.is_root_workspace);
+    let mut graph = PackageGraph::new(
+        kind,
+        PackageNode {
+            name: root_manifest.and_then(|m| m.name.clone()).unwrap_or_default(),
+            version: root_manifest.and_then(|m| m.version.clone()),
+            path: Some(String::new()),
+            ..Default::default()
+        },
+    );
+
+    // Map every descriptor to the node of the entry it resolves to
+    let mut node_of_entry = Vec::with_capacity(entries.len());
+    let mut by_descriptor: HashMap<&str, usize> = HashMap::new();
+    for (i, entry) in entries.iter().enumerate() {
+        let node = if Some(i) == root_entry {
+            ROOT
+        } else {
+            let name = entry
+                .descriptors
+                .first()
+                .and_then(|d| split_descriptor(d))
+                .map(|(name, _)| name.to_string())
+                .unwrap_or_default();
+            graph.add_node(PackageNode {
+                name,
+                version: entry.version.clone(),
+                ..Default::default()
+            })
+        };
+        node_of_entry.push(node);
+        for descriptor in &entry.descriptors {
+            by_descriptor.insert(descriptor, node);
+        }
+    }
+
+    let lookup = |name: &str, range: &str| -> Option<usize> {
+        by_descriptor
+            .get(format!("{}@{}", name, range).as_str())
+            .or_else(|| by_descriptor.get(format!("{}@npm:{}", name, range).as_str()))
+            .copied()
+    };
+
+    for (entry, &from) in entries.iter().zip(&node_of_entry) {
+        let mut edges: Vec<Edge> = Vec::new();
+        for (name, range, kind) in &entry.dependencies {
+            if let Some(to) = lookup(name, range) {
+                if !edges.iter().any(|e| e.to == to) {
+                    edges.push(Edge { to, kind: *kind });
+                }
+            }
+        }
+        graph.nodes[from].dependencies = edges;
+    }
+
+    // The root's edges come from package.json, which also knows about dev
+    // dependencies. Without it, anything nothing else depends on is a root dependency.
+    if let Some(manifest) = root_manifest {
+        let sections = [
+            (&manifest.dependencies, DependencyKind::Prod),
+            (&manifest.optional_dependencies, DependencyKind::Optional),
+            (&manifest.peer_dependencies, DependencyKind::Peer),
+            (&manifest.dev_dependencies, DependencyKind::Dev),
+        ];
+        let mut edges: Vec<Edge> = Vec::new();
+        for (section, kind) in sections {
+            let mut names: Vec<(&String, &String)> = section.iter().collect();
+            names.sort();
+            for (name, range) in names {
+                if let Some(to) = lookup(name, range) {
+                    match edges.iter_mut().find(|e| e.to == to) {
+                        // optionalDependencies are also listed under dependencies
+                        Some(edge) if kind == DependencyKind::Optional => edge.kind = kind,
+                        Some(_) => {}
+                        None => edges.push(Edge { to, kind }),
+                    }
+                }
+            }
+        }
+        graph.nodes[ROOT].dependencies = edges;
+    } else if root_entry.is_none() {
+        let depended_on: HashSet<usize> = graph
+            .nodes
+            .iter()
+            .flat_map(|n| n.dependencies.iter().map(|e| e.to))
+            .collect();
+        graph.nodes[ROOT].dependencies = (ROOT + 1..graph.nodes.len())
+            .filter(|i| !depended_on.contains(i))
+            .map(|to| Edge { to, kind: DependencyKind::Prod })
+            .collect();
+    }
+
+    graph
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dependencies(graph: &PackageGraph, node: usize) -> Vec<(String, DependencyKind)> {
+        graph.nodes[node]
+            .dependencies
+            .iter()
+            .map(|e| {
+                let to = &graph.nodes[e.to];
+                (format!("{}@{}", to.name, to.version.as_deref().unwrap_or("")), e.kind)
+            })
+            .collect()
+    }
+
+    fn find(graph: &PackageGraph, name: &str, version: &str) -> usize {
+        graph.find_by_name_version(name, version).unwrap()
+    }
+
+    #[test]
+    fn test_parse_classic() {
+        let content = r#"# THIS IS AN AUTOGENERATED FILE. DO NOT EDIT THIS FILE DIRECTLY.
+# yarn lockfile v1
+
+
+"@babel/code-frame@^7.0.0", "@babel/code-frame@^7.10.4":
+  version "7.12.13"
+  resolved "https://registry.yarnpkg.com/@babel/code-frame/-/code-frame-7.12.13.tgz"
+  integrity sha512-abc
+  dependencies:
+    "@babel/highlight" "^7.12.13"
+
+"@babel/highlight@^7.12.13":
+  version "7.13.10"
+  optionalDependencies:
+    fsevents "~2.3.1"
+
+fsevents@~2.3.1:
+  version "2.3.2"
+
+jest@^29.0.0:
+  version "29.7.0"
+  dependencies:
+    "@babel/code-frame" "^7.10.4"
+"#;
+        let manifest: RootManifest = serde_json::from_str(
+            r#"{"name": "app", "dependencies": {"@babel/code-frame": "^7.0.0"}, "devDependencies": {"jest": "^29.0.0"}}"#,
+        )
+        .unwrap();
+        let graph = parse(content, Some(&manifest)).unwrap();
+
+        assert_eq!(graph.kind, LockfileKind::YarnClassic);
+        assert_eq!(graph.nodes.len(), 5);
+        assert_eq!(
+            dependencies(&graph, ROOT),
+            vec![
+                ("@babel/code-frame@7.12.13".to_string(), DependencyKind::Prod),
+                ("jest@29.7.0".to_string(), DependencyKind::Dev),
+            ]
+        );
+        assert_eq!(
+            dependencies(&graph, find(&graph, "@babel/highlight", "7.13.10")),
+            vec![("fsevents@2.3.2".to_string(), DependencyKind::Optional)]
+        );
+        // Both descriptors resolve to the same node
+        assert_eq!(
+            dependencies(&graph, find(&graph, "jest", "29.7.0")),
+            vec![("@babel/code-frame@7.12.13".to_string(), DependencyKind::Prod)]
+        );
+
+        // Without package.json, entries nothing depends on become root dependencies
+        let graph = parse(content, None).unwrap();
+        assert_eq!(dependencies(&graph, ROOT), vec![("jest@29.7.0".to_string(), DependencyKind::Prod)]);
+    }
+
+    #[test]
+    fn test_parse_berry() {
+        let content = r#"# This file is generated by running "yarn install" inside your project.
+
+__metadata:
+  version: 6
+  cacheKey: 8
+
+"@types/node@npm:*, @types/node@npm:^18.0.0":
+  version: 18.11.9
+  resolution: "@types/node@npm:18.11.9"
+  checksum: abc
+  languageName: node
+  linkType: hard
+
+"app@workspace:.":
+  version: 0.0.0-use.local
+  resolution: "app@workspace:."
+  dependencies:
+    "@types/node": ^18.0.0
+    left-pad: 1.3.0
+  languageName: unknown
+  linkType: soft
+
+"chokidar@npm:^3.5.0":
+  version: 3.5.3
+  resolution: "chokidar@npm:3.5.3"
+  dependencies:
+    "@types/node": "*"
+    fsevents: ~2.3.2
+  dependenciesMeta:
+    fsevents:
+      optional: true
+  peerDependencies:
+    typescript: "*"
+  languageName: node
+  linkType: hard
+
+"fsevents@npm:~2.3.2":
+  version: 2.3.2
+  resolution: "fsevents@npm:2.3.2"
+  languageName: node
+  linkType: hard
+
+"left-pad@npm:1.3.0":
+  version: 1.3.0
+  resolution: "left-pad@npm:1.3.0"
+  languageName: node
+  linkType: hard
+"#;
+        let graph = parse(content, None).unwrap();
+
+        assert_eq!(graph.kind, LockfileKind::YarnBerry { version: 6 });
+        // The workspace entry is the root, not a package of its own
+        assert_eq!(graph.nodes.len(), 5);
+        assert_eq!(
+            dependencies(&graph, ROOT),
+            vec![
+                ("@types/node@18.11.9".to_string(), DependencyKind::Prod),
+                ("left-pad@1.3.0".to_string(), DependencyKind::Prod),
+            ]
+        );
+        assert_eq!(
+            dependencies(&graph, find(&graph, "chokidar", "3.5.3")),
+            vec![
+                ("@types/node@18.11.9".to_string(), DependencyKind::Prod),
+                ("fsevents@2.3.2".to_string(), DependencyKind::Optional),
+            ]
+        );
+    }
+}
@@ -0,0 +1,376 @@
+use serde::Serialize;
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::cli::{Column, OutputFormat};
+use crate::duplicates::{find_duplicates, DuplicateSet};
+use crate::lockfile::{DependencyKind, PackageGraph};
+use crate::scanner::{format_size, ModuleInfo};
+
+/// Version of the JSON document layout, bumped on incompatible changes.
+pub const REPORT_SCHEMA_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize)]
+pub struct ScanReport<'a> {
+    pub schema_version: u32,
+    pub tool: ToolInfo,
+    pub scan_root: String,
+    pub scanned_at: String,
+    /// Whether dev-only packages were left out, as with `npm install --omit=dev`
+    pub production_only: bool,
+    pub totals: Totals,
+    pub modules: &'a [ModuleInfo],
+    /// Packages installed more than once
+    pub duplicates: Vec<DuplicateSet>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ToolInfo {
+    pub name: String,
+    pub version: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Totals {
+    pub modules: usize,
+    pub size: u64,
+    pub disk_size: u64,
+    /// Bytes of hard-linked files counted once for a single package only
+    pub shared_size: u64,
+    pub files: usize,
+}
+
+impl<'a> ScanReport<'a> {
+    pub fn new(modules: &'a [ModuleInfo], scan_root: &Path, graph: Option<&PackageGraph>) -> Self {
+        let scan_root = scan_root.canonicalize().unwrap_or_else(|_| scan_root.to_path_buf());
+        ScanReport {
+            schema_version: REPORT_SCHEMA_VERSION,
+            tool: ToolInfo {
+                name: env!("CARGO_PKG_NAME").to_string(),
+                version: env!("CARGO_PKG_VERSION").to_string(),
+            },
+            scan_root: scan_root.to_string_lossy().into_owned(),
+            scanned_at: chrono::Utc::now().to_rfc3339(),
+            production_only: false,
+            totals: Totals {
+                modules: modules.len(),
+                size: modules.iter().map(|m| m.size).sum(),
+                disk_size: modules.iter().map(|m| m.disk_size).sum(),
+                shared_size: modules.iter().map(|m| m.shared_size).sum(),
+                files: modules.iter().filter_map(|m| m.files_count).sum(),
+            },
+            modules,
+            duplicates: find_duplicates(modules, graph),
+        }
+    }
+}
+
+/// Writes the scan result in the requested format.
+pub fn write_report(
+    report: &ScanReport,
+    format: OutputFormat,
+    columns: &[Column],
+    out: &mut impl Write,
+) -> io::Result<()> {
+    match format {
+        OutputFormat::Table => write_table(report, out),
+        OutputFormat::Json => write_json(report, out),
+        OutputFormat::Csv => write_delimited(report.modules, columns, ',', out),
+        OutputFormat::Tsv => write_delimited(report.modules, columns, '\t', out),
+    }
+}
+
+pub fn write_json(report: &ScanReport, out: &mut impl Write) -> io::Result<()> {
+    serde_json::to_writer_pretty(&mut *out, report)?;
+    writeln!(out)
+}
+
+/// Writes one header row and one row per module. Sizes are raw byte counts
+/// so spreadsheets can do arithmetic on them.
+pub fn write_delimited(
+    modules: &[ModuleInfo],
+    columns: &[Column],
+    delimiter: char,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    let header: Vec<&str> = columns.iter().map(|c| c.name()).collect();
+    writeln!(out, "{}", header.join(&delimiter.to_string()))?;
+
+    for module in modules {
+        let fields: Vec<String> = columns
+            .iter()
+            .map(|column| escape_field(&column_value(module, *column), delimiter))
+            .collect();
+        writeln!(out, "{}", fields.join(&delimiter.to_string()))?;
+    }
+    Ok(())
+}
+
+fn column_value(module: &ModuleInfo, column: Column) -> String {
+    fn optional<T: ToString>(value: &Option<T>) -> String {
+        value.as_ref().map(|v| v.to_string()).unwrap_or_default()
+    }
+
+    match column {
+        Column::Name => module.name.clone(),
+        Column::Version => optional(&module.version),
+        Column::Size => module.size.to_string(),
+        Column::DiskSize => module.disk_size.to_string(),
+        Column::FilesCount => optional(&module.files_count),
+        Column::DependencyCount => optional(&module.dependency_count),
+        Column::License => optional(&module.license),
+        Column::IsDevDependency => module.is_dev_dependency.to_string(),
+        Column::DependencyKind => module.dependency_kind.map(|k| k.label()).unwrap_or_default().to_string(),
+        Column::Path => module.path.clone(),
+    }
+}
+
+/// Quotes CSV fields as per RFC 4180. TSV has no quoting, so tabs and line
+/// breaks are replaced by spaces instead.
+fn escape_field(value: &str, delimiter: char) -> String {
+    if delimiter == '\t' {
+        value.replace(['\t', '\n', '\r'], " ")
+    } else if value.contains([delimiter, '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Writes the modules as a plain text table, followed by the totals and the
+/// duplicated packages.
+pub fn write_table(report: &ScanReport, out: &mut impl Write) -> io::Result<()> {
+    let modules = report.modules;
+    let names: Vec<String> = modules
+        .iter()
+        .map(|m| {
+            let mut name = match m.parent_name() {
+                Some(parent) => format!("{} (in {})", m.name, parent),
+                None => m.name.clone(),
+            };
+            match m.dependency_kind {
+                Some(DependencyKind::Prod) | None => {}
+                Some(kind) => name.push_str(&format!(" [{}]", kind.label())),
+            }
+            name
+        })
+        .collect();
+    let name_width = names.iter().map(|n| n.len()).max().unwrap_or(0).max("MODULE".len());
+    let version_width = modules
+        .iter()
+        .filter_map(|m| m.version.as_ref().map(|v| v.len()))
+        .max()
+        .unwrap_or(0)
+        .max("VERSION".len());
+
+    writeln!(
+        out,
+        "{:<name_width$}  {:<version_width$}  {:>10}  {:>10}",
+        "MODULE", "VERSION", "SIZE", "DISK"
+    )?;
+    for (module, name) in modules.iter().zip(&names) {
+        writeln!(
+            out,
+            "{:<name_width$}  {:<version_width$}  {:>10}  {:>10}",
+            name,
+            module.version.as_deref().unwrap_or("-"),
+            format_size(module.size),
+            format_size(module.disk_size),
+        )?;
+    }
+
+    writeln!(out)?;
+    let scope = if report.production_only { " (production only)" } else { "" };
+    writeln!(
+        out,
+        "Total: {} modules, {}, {} on disk{}",
+        modules.len(),
+        format_size(report.totals.size),
+        format_size(report.totals.disk_size),
+        scope
+    )?;
+    if report.totals.shared_size > 0 {
+        writeln!(
+            out,
+            "Hard links: {} shared between packages, counted once",
+            format_size(report.totals.shared_size)
+        )?;
+    }
+    let by_kind: Vec<String> = totals_by_kind(modules)
+        .iter()
+        .map(|(kind, count, size)| format!("{} {} ({})", kind.label(), format_size(*size), count))
+        .collect();
+    if !by_kind.is_empty() {
+        writeln!(out, "By type: {}", by_kind.join(", "))?;
+    }
+
+    if !report.duplicates.is_empty() {
+        let wasted: u64 = report.duplicates.iter().map(|d| d.wasted).sum();
+        writeln!(out)?;
+        writeln!(
+            out,
+            "Duplicates: {} packages installed more than once, {} wasted",
+            report.duplicates.len(),
+            format_size(wasted)
+        )?;
+        for set in &report.duplicates {
+            writeln!(out, "  {} ({} copies, {} wasted)", set.name, set.copies.len(), format_size(set.wasted))?;
+            for copy in &set.copies {
+                let required_by = if copy.dependents.is_empty() {
+                    String::new()
+                } else {
+                    format!(", required by {}", copy.dependents.join(", "))
+                };
+                writeln!(
+                    out,
+                    "    {} at {}, {}{}",
+                    copy.version.as_deref().unwrap_or("?"),
+                    copy.path,
+                    format_size(copy.size),
+                    required_by
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Number and size of the modules of every dependency kind that occurs,
+/// strongest kind first. Unclassified modules are left out.
+pub fn totals_by_kind(modules: &[ModuleInfo]) -> Vec<(DependencyKind, usize, u64)> {
+    DependencyKind::ALL
+        .iter()
+        .filter_map(|&kind| {
+            let (count, size) = modules
+                .iter()
+                .filter(|m| m.dependency_kind == Some(kind))
+                .fold((0, 0), |(count, size), m| (count + 1, size + m.size));
+            (count > 0).then_some((kind, count, size))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::{scan_modules_dir, ScanOptions};
+    use std::fs::{self, File};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_write_table() -> io::Result<()> {
+        let temp_dir = tempdir()?;
+        let module_path = temp_dir.path().join("left-pad");
+        fs::create_dir(&module_path)?;
+        File::create(module_path.join("index.js"))?.write_all(&[b'a'; 2048])?;
+        File::create(module_path.join("package.json"))?.write_all(br#"{"version": "1.3.0"}"#)?;
+
+        let mut modules = scan_modules_dir(temp_dir.path(), &ScanOptions::default())?.modules;
+        modules[0].disk_size = 8192;
+        let mut out = Vec::new();
+        write_table(&ScanReport::new(&modules, temp_dir.path(), None), &mut out)?;
+        let text = String::from_utf8(out).unwrap();
+
+        let lines: Vec<&str> = text.lines().collect();
+        assert!(lines[0].starts_with("MODULE"));
+        assert!(lines[1].starts_with("left-pad  1.3.0"));
+        assert!(lines[1].ends_with("2.02 KB     8.00 KB"));
+        assert_eq!(lines[3], "Total: 1 modules, 2.02 KB, 8.00 KB on disk");
+        assert_eq!(lines.len(), 4);
+
+        modules[0].dependency_kind = Some(DependencyKind::Dev);
+        modules[0].shared_size = 1024;
+        let report = ScanReport {
+            production_only: true,
+            ..ScanReport::new(&modules, temp_dir.path(), None)
+        };
+        let mut out = Vec::new();
+        write_table(&report, &mut out)?;
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert!(lines[1].starts_with("left-pad [dev]  1.3.0"));
+        assert_eq!(lines[3], "Total: 1 modules, 2.02 KB, 8.00 KB on disk (production only)");
+        assert_eq!(lines[4], "Hard links: 1.00 KB shared between packages, counted once");
+        assert_eq!(lines[5], "By type: dev 2.02 KB (1)");
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_table_duplicates() -> io::Result<()> {
+        let temp_dir = tempdir()?;
+        for (path, version) in [("a", "1.0.0"), ("b", "2.0.0"), ("a/node_modules/b", "1.0.0")] {
+            let module_path = temp_dir.path().join(path);
+            fs::create_dir_all(&module_path)?;
+            fs::write(module_path.join("package.json"), format!(r#"{{"version": "{}"}}"#, version))?;
+        }
+
+        let modules = scan_modules_dir(temp_dir.path(), &ScanOptions::default())?.modules;
+        let mut out = Vec::new();
+        write_table(&ScanReport::new(&modules, temp_dir.path(), None), &mut out)?;
+        let text = String::from_utf8(out).unwrap();
+
+        let section: Vec<&str> = text.lines().skip_while(|l| !l.starts_with("Duplicates:")).collect();
+        assert_eq!(
+            section,
+            [
+                "Duplicates: 1 packages installed more than once, 20 B wasted",
+                "  b (2 copies, 20 B wasted)",
+                "    2.0.0 at node_modules/b, 20 B",
+                "    1.0.0 at node_modules/a/node_modules/b, 20 B, required by a",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_json() -> io::Result<()> {
+        let temp_dir = tempdir()?;
+        let module_path = temp_dir.path().join("left-pad");
+        fs::create_dir(&module_path)?;
+        File::create(module_path.join("index.js"))?.write_all(&[b'a'; 100])?;
+
+        let modules = scan_modules_dir(temp_dir.path(), &ScanOptions::default())?.modules;
+        let mut out = Vec::new();
+        write_report(&ScanReport::new(&modules, temp_dir.path(), None), OutputFormat::Json, &Column::ALL, &mut out)?;
+        let json: serde_json::Value = serde_json::from_slice(&out)?;
+
+        assert_eq!(json["schema_version"], REPORT_SCHEMA_VERSION);
+        assert_eq!(json["tool"]["name"], "node-size-analyzer");
+        assert_eq!(json["totals"]["modules"], 1);
+        assert_eq!(json["totals"]["size"], 100);
+        assert_eq!(json["totals"]["files"], 1);
+        assert_eq!(json["modules"][0]["name"], "left-pad");
+        assert_eq!(json["modules"][0]["path"], "node_modules/left-pad");
+        assert_eq!(json["duplicates"], serde_json::json!([]));
+        assert!(json["scanned_at"].as_str().is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_delimited() -> io::Result<()> {
+        let temp_dir = tempdir()?;
+        let module_path = temp_dir.path().join("left-pad");
+        fs::create_dir(&module_path)?;
+        File::create(module_path.join("package.json"))?
+            .write_all(br#"{"version": "1.3.0", "license": "MIT, \"or\" ISC"}"#)?;
+
+        let modules = scan_modules_dir(temp_dir.path(), &ScanOptions::default())?.modules;
+        let report = ScanReport::new(&modules, temp_dir.path(), None);
+        let columns = [Column::Name, Column::Version, Column::License, Column::IsDevDependency];
+
+        let mut out = Vec::new();
+        write_report(&report, OutputFormat::Csv, &columns, &mut out)?;
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "name,version,license,is_dev_dependency\nleft-pad,1.3.0,\"MIT, \"\"or\"\" ISC\",false\n"
+        );
+
+        let mut out = Vec::new();
+        write_report(&report, OutputFormat::Tsv, &columns, &mut out)?;
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "name\tversion\tlicense\tis_dev_dependency\nleft-pad\t1.3.0\tMIT, \"or\" ISC\tfalse\n"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,1474 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    fs::{self, DirEntry},
+    io,
+    ops::AddAssign,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::Sender,
+    },
+    thread,
+    time::UNIX_EPOCH,
+};
+
+use crate::cache::{CachedPackage, Fingerprint, ScanCache};
+use crate::cli::{SizeMetric, SymlinkPolicy};
+use crate::lockfile::DependencyKind;
+
+#[derive(Debug, Deserialize, Serialize)]
+struct PackageJson {
+    name: Option<String>,
+    version: Option<String>,
+    description: Option<String>,
+    author: Option<Person>,
+    license: Option<License>,
+    /// Deprecated list of licenses, still found in older packages
+    licenses: Option<Vec<License>>,
+    homepage: Option<String>,
+    repository: Option<Repository>,
+    dependencies: Option<HashMap<String, String>>,
+    #[serde(rename = "devDependencies")]
+    dev_dependencies: Option<HashMap<String, String>>,
+    #[serde(rename = "peerDependencies")]
+    peer_dependencies: Option<HashMap<String, String>>,
+    #[serde(rename = "optionalDependencies")]
+    optional_dependencies: Option<HashMap<String, String>>,
+    #[serde(rename = "publishConfig")]
+    publish_config: Option<HashMap<String, serde_json::Value>>,
+}
+
+/// A person field, either `"Name <email> (url)"` or an object.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(untagged)]
+enum Person {
+    Text(String),
+    Object {
+        name: Option<String>,
+        email: Option<String>,
+        url: Option<String>,
+    },
+}
+
+impl Person {
+    /// The person in npm's `Name <email> (url)` form.
+    fn to_string(&self) -> Option<String> {
+        match self {
+            Person::Text(text) => Some(text.clone()),
+            Person::Object { name, email, url } => {
+                let mut person = name.clone()?;
+                if let Some(email) = email {
+                    person.push_str(&format!(" <{}>", email));
+                }
+                if let Some(url) = url {
+                    person.push_str(&format!(" ({})", url));
+                }
+                Some(person)
+            }
+        }
+    }
+}
+
+/// A license, either an SPDX expression or the deprecated `{ type, url }` object.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(untagged)]
+enum License {
+    Expression(String),
+    Object {
+        #[serde(rename = "type")]
+        license_type: Option<String>,
+        url: Option<String>,
+    },
+}
+
+impl License {
+    fn to_string(&self) -> Option<String> {
+        match self {
+            License::Expression(expression) => Some(expression.clone()),
+            License::Object { license_type, url } => license_type.clone().or_else(|| url.clone()),
+        }
+    }
+}
+
+/// A repository, either an object with a URL or a shorthand like
+/// `github:user/repo`, `gitlab:user/repo` or just `user/repo`.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(untagged)]
+enum Repository {
+    Shorthand(String),
+    Object {
+        #[serde(rename = "type")]
+        repo_type: Option<String>,
+        url: Option<String>,
+    },
+}
+
+impl Repository {
+    fn to_string(&self) -> Option<String> {
+        match self {
+            Repository::Shorthand(shorthand) => Some(expand_repository(shorthand)),
+            Repository::Object { url, .. } => url.as_deref().map(expand_repository),
+        }
+    }
+}
+
+/// Expands a repository shorthand to the URL npm would use, leaving full URLs as they are.
+fn expand_repository(repository: &str) -> String {
+    const HOSTS: [(&str, &str); 4] = [
+        ("github:", "https://github.com/"),
+        ("gitlab:", "https://gitlab.com/"),
+        ("bitbucket:", "https://bitbucket.org/"),
+        ("gist:", "https://gist.github.com/"),
+    ];
+    for (prefix, host) in HOSTS {
+        if let Some(path) = repository.strip_prefix(prefix) {
+            return format!("{}{}", host, path);
+        }
+    }
+    let is_github_path = !repository.contains(':')
+        && repository.split('/').count() == 2
+        && repository.split('/').all(|part| !part.is_empty());
+    if is_github_path {
+        return format!("https://github.com/{}", repository);
+    }
+    repository.to_string()
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ModuleInfo {
+    pub name: String,
+    /// Size of the package itself, excluding its nested `node_modules`
+    pub size: u64,
+    /// Size of all packages installed in the package's nested `node_modules`
+    pub nested_size: u64,
+    /// Disk space allocated to the package itself, in whole filesystem blocks
+    #[serde(default)]
+    pub disk_size: u64,
+    /// Disk space allocated to the packages in the nested `node_modules`
+    #[serde(default)]
+    pub nested_disk_size: u64,
+    /// Bytes of files hard-linked to a file already counted elsewhere in the
+    /// scan. They are left out of `size` and `disk_size`, so every physical
+    /// file is only counted once in the totals.
+    #[serde(default)]
+    pub shared_size: u64,
+    /// Install path relative to the project root, e.g. `node_modules/a/node_modules/b`
+    pub path: String,
+    /// Install path of the package whose `node_modules` this one lives in
+    pub parent: Option<String>,
+    /// Where the package directory points to, for packages symlinked into
+    /// `node_modules` from elsewhere
+    pub link_target: Option<String>,
+    /// Some files or directories of the package could not be read, so its
+    /// sizes and file counts are too low
+    #[serde(default)]
+    pub partial: bool,
+    /// Number of `node_modules` levels below the top-level one
+    pub depth: usize,
+    pub dependency_count: Option<usize>,
+    pub last_updated: Option<String>,
+    /// When the package directory was last modified, in seconds since the epoch
+    #[serde(default)]
+    pub last_modified: Option<u64>,
+    pub license: Option<String>,
+    pub version: Option<String>,
+    pub description: Option<String>,
+    pub author: Option<String>,
+    pub homepage: Option<String>,
+    pub repository: Option<String>,
+    pub files_count: Option<usize>,
+    pub file_types: Option<Vec<(String, usize)>>,  // (extension, count)
+    pub is_dev_dependency: bool,
+    /// Strongest way the project depends on this package, `None` when there
+    /// is no dependency graph or the package is not reachable from the root
+    pub dependency_kind: Option<DependencyKind>,
+}
+
+impl ModuleInfo {
+    /// Returns the `@scope` part of a scoped package name, if any.
+    pub fn scope(&self) -> Option<&str> {
+        if self.name.starts_with('@') {
+            self.name.split_once('/').map(|(scope, _)| scope)
+        } else {
+            None
+        }
+    }
+
+    /// Size of the package including every package nested below it.
+    pub fn total_size(&self) -> u64 {
+        self.size + self.nested_size
+    }
+
+    /// Own size of the package as measured by `metric`.
+    pub fn size_by(&self, metric: SizeMetric) -> u64 {
+        match metric {
+            SizeMetric::Apparent => self.size,
+            SizeMetric::Disk => self.disk_size,
+        }
+    }
+
+    /// Name of the package this instance is nested in, if any.
+    pub fn parent_name(&self) -> Option<&str> {
+        self.parent.as_deref().map(package_name_from_path)
+    }
+}
+
+/// Extracts the package name from an install path such as `node_modules/a/node_modules/@s/b`.
+pub fn package_name_from_path(path: &str) -> &str {
+    match path.rfind("node_modules/") {
+        Some(idx) => &path[idx + "node_modules/".len()..],
+        None => path,
+    }
+}
+
+/// A file or directory that could not be read during a scan.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScanError {
+    pub path: String,
+    pub message: String,
+}
+
+impl ScanError {
+    fn new(path: &Path, err: &io::Error) -> Self {
+        ScanError {
+            path: path.display().to_string(),
+            message: err.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ScanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "cannot read {}: {}", self.path, self.message)
+    }
+}
+
+/// Size of a file tree, both as the sum of file lengths and as the disk
+/// space allocated to it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DirSize {
+    pub apparent: u64,
+    /// Allocated blocks of files and directories, like `du` reports. Equal to
+    /// the apparent size on platforms without block counts.
+    pub disk: u64,
+    /// Apparent size of files left out because another package owns a hard
+    /// link to them
+    pub shared: u64,
+}
+
+impl AddAssign for DirSize {
+    fn add_assign(&mut self, other: DirSize) {
+        self.apparent += other.apparent;
+        self.disk += other.disk;
+        self.shared += other.shared;
+    }
+}
+
+/// Everything collected in a single walk over a file tree.
+#[derive(Debug, Default)]
+struct TreeStats {
+    size: DirSize,
+    files: usize,
+    /// Number of files per lowercase extension
+    extensions: HashMap<String, usize>,
+    fingerprint: Fingerprint,
+    /// Files with more than one hard link, left out of `size` until it is
+    /// known which package owns them
+    links: Vec<LinkedFile>,
+    /// Files and directories that could not be read and were left out
+    errors: Vec<ScanError>,
+}
+
+/// A file with more than one hard link. Package managers such as pnpm
+/// hard-link identical files from a global store, which would otherwise be
+/// counted once per link.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LinkedFile {
+    pub dev: u64,
+    pub ino: u64,
+    pub size: u64,
+    pub disk: u64,
+}
+
+#[cfg(unix)]
+fn linked_file(metadata: &fs::Metadata) -> Option<LinkedFile> {
+    use std::os::unix::fs::MetadataExt;
+    (metadata.nlink() > 1).then(|| LinkedFile {
+        dev: metadata.dev(),
+        ino: metadata.ino(),
+        size: metadata.len(),
+        disk: allocated_size(metadata),
+    })
+}
+
+#[cfg(not(unix))]
+fn linked_file(_metadata: &fs::Metadata) -> Option<LinkedFile> {
+    None
+}
+
+#[cfg(unix)]
+fn allocated_size(metadata: &fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    // st_blocks is always counted in 512 byte units
+    metadata.blocks() * 512
+}
+
+#[cfg(not(unix))]
+fn allocated_size(metadata: &fs::Metadata) -> u64 {
+    metadata.len()
+}
+
+/// Walks a file tree once, measuring sizes and counting files by extension.
+/// Whatever cannot be read is left out and recorded in the errors.
+fn get_tree_stats(path: &Path, skip_node_modules: bool) -> TreeStats {
+    let mut stats = TreeStats::default();
+    if let Ok(metadata) = fs::metadata(path) {
+        stats.fingerprint.set_root(&metadata);
+    }
+    walk_tree(path, skip_node_modules, &mut stats);
+    stats
+}
+
+fn walk_tree(path: &Path, skip_node_modules: bool, stats: &mut TreeStats) {
+    let entries = fs::metadata(path).and_then(|metadata| {
+        stats.size.disk += allocated_size(&metadata);
+        stats.fingerprint.add_dir(&metadata);
+        fs::read_dir(path)
+    });
+    let entries = match entries {
+        Ok(entries) => entries,
+        Err(err) => return stats.errors.push(ScanError::new(path, &err)),
+    };
+    for entry in entries {
+        let result = match entry {
+            Ok(entry) => add_tree_entry(&entry, skip_node_modules, stats).map_err(|err| ScanError::new(&entry.path(), &err)),
+            Err(err) => Err(ScanError::new(path, &err)),
+        };
+        if let Err(error) = result {
+            stats.errors.push(error);
+        }
+    }
+}
+
+fn add_tree_entry(entry: &DirEntry, skip_node_modules: bool, stats: &mut TreeStats) -> io::Result<()> {
+    if skip_node_modules && entry.file_name() == "node_modules" {
+        return Ok(());
+    }
+    stats.fingerprint.entries += 1;
+    // Symlinks are not followed, their target is either sized where it
+    // really lives or is not part of node_modules at all
+    let file_type = entry.file_type()?;
+    if file_type.is_file() {
+        let metadata = entry.metadata()?;
+        if let Some(link) = linked_file(&metadata) {
+            stats.links.push(link);
+        } else {
+            stats.size.apparent += metadata.len();
+            stats.size.disk += allocated_size(&metadata);
+        }
+
+        stats.files += 1;
+        let extension = match Path::new(&entry.file_name()).extension() {
+            Some(extension) => extension.to_string_lossy().to_lowercase(),
+            None => "(no extension)".to_string(),
+        };
+        *stats.extensions.entry(extension).or_insert(0) += 1;
+    } else if file_type.is_dir() {
+        walk_tree(&entry.path(), false, stats);
+    }
+    Ok(())
+}
+
+/// Fingerprint of a package directory, leaving out its nested `node_modules`.
+/// Only directories are read, which is much cheaper than measuring the files.
+pub fn get_fingerprint(path: &Path) -> io::Result<Fingerprint> {
+    let mut fingerprint = Fingerprint::default();
+    fingerprint.set_root(&fs::metadata(path)?);
+    walk_dirs(path, true, &mut fingerprint)?;
+    Ok(fingerprint)
+}
+
+fn walk_dirs(path: &Path, skip_node_modules: bool, fingerprint: &mut Fingerprint) -> io::Result<()> {
+    fingerprint.add_dir(&fs::metadata(path)?);
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        if skip_node_modules && entry.file_name() == "node_modules" {
+            continue;
+        }
+        fingerprint.entries += 1;
+        if entry.file_type()?.is_dir() {
+            walk_dirs(&entry.path(), false, fingerprint)?;
+        }
+    }
+    Ok(())
+}
+
+/// pnpm keeps the real packages in the `.pnpm` virtual store and only puts
+/// symlinks into `node_modules`.
+pub fn is_pnpm_layout(node_modules: &Path) -> bool {
+    node_modules.join(".pnpm").is_dir()
+}
+
+pub fn format_size(size: u64) -> String {
+    const KB: u64 = 1024;
+    const MB: u64 = KB * 1024;
+
+    if size >= MB {
+        format!("{:.2} MB", size as f64 / MB as f64)
+    } else if size >= KB {
+        format!("{:.2} KB", size as f64 / KB as f64)
+    } else {
+        format!("{} B", size)
+    }
+}
+
+/// Settings for scanning a `node_modules` directory.
+#[derive(Debug, Clone, Default)]
+pub struct ScanOptions {
+    pub symlinks: SymlinkPolicy,
+    /// Number of threads measuring packages, at least one is always used
+    pub threads: usize,
+    /// Receives the packages as they are measured
+    pub progress: Option<Sender<ScanEvent>>,
+    /// Directory keeping the results of earlier scans, unchanged packages
+    /// are taken from there instead of being measured again
+    pub cache_dir: Option<PathBuf>,
+}
+
+/// Progress of a running scan.
+#[derive(Debug)]
+pub enum ScanEvent {
+    /// This many package directories were found and are about to be measured
+    Listed(usize),
+    /// A package was measured. Its nested sizes are only known once the
+    /// whole scan is done.
+    Measured(Box<ModuleInfo>),
+}
+
+/// The packages found by a scan, and the paths it could not read.
+#[derive(Debug, Default)]
+pub struct ScanOutput {
+    pub modules: Vec<ModuleInfo>,
+    pub errors: Vec<ScanError>,
+}
+
+/// Scans a `node_modules` directory. The package directories are listed
+/// first, then measured in parallel with a single walk over each of them.
+/// Only an unreadable `node_modules` fails the scan; anything below it that
+/// cannot be read is reported in the errors and left out.
+pub fn scan_modules_dir(node_modules: &Path, options: &ScanOptions) -> io::Result<ScanOutput> {
+    let mut discovery = Discovery {
+        symlinks: options.symlinks,
+        root: fs::canonicalize(node_modules)?,
+        ..Discovery::default()
+    };
+    let level = Level {
+        prefix: "node_modules".to_string(),
+        parent: None,
+        depth: 0,
+    };
+    find_packages(node_modules, &level, &mut discovery)?;
+    if is_pnpm_layout(node_modules) {
+        let store = node_modules.join(".pnpm");
+        if let Err(err) = find_pnpm_store_packages(&store, &mut discovery) {
+            discovery.errors.push(ScanError::new(&store, &err));
+        }
+    }
+
+    if let Some(progress) = &options.progress {
+        // Nobody listening any more is no reason to stop
+        let _ = progress.send(ScanEvent::Listed(discovery.packages.len()));
+    }
+    let cache = options.cache_dir.as_deref().map(|dir| ScanCache::open(dir, &discovery.root));
+    let mut measured = measure_packages(discovery.packages, options, cache.as_ref());
+    if let Some(cache) = &cache {
+        let packages = measured
+            .iter()
+            .filter_map(|measured| {
+                let cached = CachedPackage {
+                    fingerprint: measured.fingerprint?,
+                    module: measured.module.clone(),
+                    links: measured.links.clone(),
+                };
+                Some((measured.module.path.clone(), cached))
+            })
+            .collect();
+        // A cache that cannot be written only costs time on the next scan
+        let _ = cache.save(packages);
+    }
+    // Cached packages take part too, the owner of a file may have changed
+    assign_hard_links(&mut measured);
+
+    let mut output = ScanOutput {
+        modules: Vec::with_capacity(measured.len()),
+        errors: discovery.errors,
+    };
+    for measured in measured {
+        output.modules.push(measured.module);
+        output.errors.extend(measured.errors);
+    }
+    update_nested_sizes(&mut output.modules, &[]);
+    output.modules.sort_by_key(|m| std::cmp::Reverse(m.size));
+    Ok(output)
+}
+
+/// Keeps the modules matching `keep` and recomputes the nested sizes of the
+/// remaining ones so they only include kept modules.
+pub fn retain_modules(modules: &mut Vec<ModuleInfo>, keep: impl Fn(&ModuleInfo) -> bool) {
+    let kept: Vec<bool> = modules.iter().map(&keep).collect();
+    update_nested_sizes(modules, &kept);
+    let mut kept = kept.into_iter();
+    modules.retain(|_| kept.next().unwrap());
+}
+
+/// Sets the nested sizes of every module from the modules installed below
+/// it. Only modules flagged in `kept` are counted, all of them when it is empty.
+fn update_nested_sizes(modules: &mut [ModuleInfo], kept: &[bool]) {
+    let is_kept = |index: usize| kept.get(index).copied().unwrap_or(true);
+    let by_path: HashMap<&str, usize> = modules.iter().enumerate().map(|(i, m)| (m.path.as_str(), i)).collect();
+    let mut nested_sizes = vec![DirSize::default(); modules.len()];
+    for (_, module) in modules.iter().enumerate().filter(|&(i, _)| is_kept(i)) {
+        let mut parent = module.parent.as_deref().and_then(|p| by_path.get(p));
+        while let Some(&index) = parent {
+            if is_kept(index) {
+                nested_sizes[index] += DirSize {
+                    apparent: module.size,
+                    disk: module.disk_size,
+                    shared: module.shared_size,
+                };
+            }
+            parent = modules[index].parent.as_deref().and_then(|p| by_path.get(p));
+        }
+    }
+
+    for (module, nested) in modules.iter_mut().zip(nested_sizes) {
+        module.nested_size = nested.apparent;
+        module.nested_disk_size = nested.disk;
+    }
+}
+
+/// A package directory waiting to be measured.
+struct PackageDir {
+    path: PathBuf,
+    /// Name and place in the install tree, the rest is filled in when measured
+    module: ModuleInfo,
+    /// False for linked packages that are only listed, see [`SymlinkPolicy`]
+    measure: bool,
+}
+
+/// Package directories found so far while listing a `node_modules` tree.
+#[derive(Default)]
+struct Discovery {
+    packages: Vec<PackageDir>,
+    symlinks: SymlinkPolicy,
+    /// Canonical path of the scanned `node_modules` directory
+    root: PathBuf,
+    /// Canonical targets of the linked packages measured so far. A link to
+    /// one of them, including one that leads back up the tree, is listed
+    /// without being measured again.
+    followed: HashSet<PathBuf>,
+    /// Directories and links that could not be read, with whatever they hold
+    errors: Vec<ScanError>,
+}
+
+/// Where a `node_modules` directory sits in the install tree.
+struct Level<'a> {
+    /// Install path of the directory itself, relative to the project root
+    prefix: String,
+    /// Install path of the package owning the directory
+    parent: Option<&'a str>,
+    depth: usize,
+}
+
+impl Level<'_> {
+    /// Places a package found in this directory in the install tree.
+    fn place(&self, module: &mut ModuleInfo) {
+        module.path = format!("{}/{}", self.prefix, module.name);
+        module.parent = self.parent.map(str::to_string);
+        module.depth = self.depth;
+    }
+}
+
+/// Lists the packages of one `node_modules` directory and recurses into
+/// nested ones. Only failing to read `dir` itself is an error, entries that
+/// cannot be read are recorded in the discovery and skipped.
+fn find_packages(dir: &Path, level: &Level, discovery: &mut Discovery) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let result = match entry {
+            Ok(entry) => find_entry(&entry, level, discovery).map_err(|err| ScanError::new(&entry.path(), &err)),
+            Err(err) => Err(ScanError::new(dir, &err)),
+        };
+        if let Err(error) = result {
+            discovery.errors.push(error);
+        }
+    }
+    Ok(())
+}
+
+/// Lists the package, or the packages of the scope, at one entry of a
+/// `node_modules` directory.
+fn find_entry(entry: &DirEntry, level: &Level, discovery: &mut Discovery) -> io::Result<()> {
+    let path = entry.path();
+    let name = entry.file_name().to_string_lossy().into_owned();
+    // Dot entries belong to the package manager: .bin, .cache, .pnpm,
+    // .yarn-state and the like
+    if name.starts_with('.') || !is_dir(&path, discovery) {
+        return Ok(());
+    }
+    let is_link = entry.file_type()?.is_symlink();
+
+    if !name.starts_with('@') {
+        return find_package(path, is_link, name, level, discovery);
+    }
+    if is_link {
+        return Ok(());
+    }
+    // Scope directories are namespaces, every child is a package of its own
+    for scoped_entry in fs::read_dir(&path)? {
+        let scoped_entry = match scoped_entry {
+            Ok(scoped_entry) => scoped_entry,
+            Err(err) => {
+                discovery.errors.push(ScanError::new(&path, &err));
+                continue;
+            }
+        };
+        let scoped_path = scoped_entry.path();
+        if scoped_entry.file_name().to_string_lossy().starts_with('.') || !is_dir(&scoped_path, discovery) {
+            continue;
+        }
+        let package_name = scoped_path.file_name().unwrap().to_string_lossy();
+        let name = format!("{}/{}", name, package_name);
+        let result = scoped_entry
+            .file_type()
+            .and_then(|file_type| find_package(scoped_path.clone(), file_type.is_symlink(), name, level, discovery));
+        if let Err(err) = result {
+            discovery.errors.push(ScanError::new(&scoped_path, &err));
+        }
+    }
+    Ok(())
+}
+
+/// Whether `path` is a directory or a link to one. A link to nowhere is
+/// recorded as an error, it is most likely a package that went missing.
+fn is_dir(path: &Path, discovery: &mut Discovery) -> bool {
+    match fs::metadata(path) {
+        Ok(metadata) => metadata.is_dir(),
+        Err(err) => {
+            discovery.errors.push(ScanError::new(path, &err));
+            false
+        }
+    }
+}
+
+/// Lists the packages of the pnpm virtual store. Every
+/// `.pnpm/<name>@<version>/node_modules` holds the real directory of one
+/// package next to symlinks to its dependencies, so skipping symlinks lists
+/// each package exactly once.
+fn find_pnpm_store_packages(store: &Path, discovery: &mut Discovery) -> io::Result<()> {
+    for entry in fs::read_dir(store)? {
+        let (entry, file_type) = match entry.and_then(|entry| Ok((entry.file_type()?, entry))) {
+            Ok((file_type, entry)) => (entry, file_type),
+            Err(err) => {
+                discovery.errors.push(ScanError::new(store, &err));
+                continue;
+            }
+        };
+        let name = entry.file_name().to_string_lossy().into_owned();
+        // .pnpm/node_modules only holds symlinks for hoisting
+        if !file_type.is_dir() || name == "node_modules" {
+            continue;
+        }
+        let store_modules = entry.path().join("node_modules");
+        if store_modules.is_dir() {
+            let level = Level {
+                prefix: format!("node_modules/.pnpm/{}/node_modules", name),
+                parent: None,
+                depth: 0,
+            };
+            if let Err(err) = find_packages(&store_modules, &level, discovery) {
+                discovery.errors.push(ScanError::new(&store_modules, &err));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Lists a package directory, applying the symlink policy when it is a link.
+fn find_package(
+    path: PathBuf,
+    is_link: bool,
+    name: String,
+    level: &Level,
+    discovery: &mut Discovery,
+) -> io::Result<()> {
+    let mut module = ModuleInfo {
+        name,
+        ..ModuleInfo::default()
+    };
+    level.place(&mut module);
+
+    let mut measure = true;
+    if is_link {
+        let target = fs::canonicalize(&path)?;
+        // Links within node_modules, like all of pnpm's, lead to a directory
+        // that is counted where it really lives
+        if target.starts_with(&discovery.root) || discovery.symlinks == SymlinkPolicy::Skip {
+            return Ok(());
+        }
+        module.link_target = Some(fs::read_link(&path)?.to_string_lossy().into_owned());
+        measure = discovery.symlinks == SymlinkPolicy::Follow && discovery.followed.insert(target);
+    }
+
+    let nested = path.join("node_modules");
+    let module_path = module.path.clone();
+    discovery.packages.push(PackageDir { path, module, measure });
+    if measure && nested.is_dir() {
+        let nested_level = Level {
+            prefix: format!("{}/node_modules", module_path),
+            parent: Some(&module_path),
+            depth: level.depth + 1,
+        };
+        if let Err(err) = find_packages(&nested, &nested_level, discovery) {
+            discovery.errors.push(ScanError::new(&nested, &err));
+        }
+    }
+    Ok(())
+}
+
+/// A measured package.
+struct Measured {
+    module: ModuleInfo,
+    /// Fingerprint of the package directory, only for packages that were
+    /// fully measured and can be cached
+    fingerprint: Option<Fingerprint>,
+    /// Hard-linked files in the package, not yet counted in its size
+    links: Vec<LinkedFile>,
+    /// What could not be read in the package directory
+    errors: Vec<ScanError>,
+}
+
+/// Measures the listed packages on a pool of worker threads. The modules
+/// come back in the order the packages were listed in.
+fn measure_packages(
+    packages: Vec<PackageDir>,
+    options: &ScanOptions,
+    cache: Option<&ScanCache>,
+) -> Vec<Measured> {
+    let next = AtomicUsize::new(0);
+    let mut results: Vec<(usize, Measured)> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..options.threads.clamp(1, packages.len().max(1)))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut results = Vec::new();
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(package) = packages.get(index) else {
+                            return results;
+                        };
+                        let measured = measure_package(package, cache);
+                        if let Some(progress) = &options.progress {
+                            // Until the owners are known every hard link is
+                            // shown as part of the package
+                            let mut module = measured.module.clone();
+                            for link in &measured.links {
+                                module.size += link.size;
+                                module.disk_size += link.disk;
+                            }
+                            let _ = progress.send(ScanEvent::Measured(Box::new(module)));
+                        }
+                        results.push((index, measured));
+                    }
+                })
+            })
+            .collect();
+        workers.into_iter().flat_map(|worker| worker.join().unwrap()).collect()
+    });
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, measured)| measured).collect()
+}
+
+/// Counts every hard-linked file in the size of the first package listed
+/// that links it and as shared in all others. Done in listing order after
+/// measuring, so the owner does not depend on which thread got there first.
+fn assign_hard_links(measured: &mut [Measured]) {
+    let mut owned = HashSet::new();
+    for measured in measured {
+        let module = &mut measured.module;
+        for link in &measured.links {
+            if owned.insert((link.dev, link.ino)) {
+                module.size += link.size;
+                module.disk_size += link.disk;
+            } else {
+                module.shared_size += link.size;
+            }
+        }
+    }
+}
+
+fn measure_package(package: &PackageDir, cache: Option<&ScanCache>) -> Measured {
+    let path = &package.path;
+    let mut module = package.module.clone();
+    if !package.measure {
+        // Only the link itself is counted
+        let mut errors = Vec::new();
+        match fs::symlink_metadata(path) {
+            Ok(metadata) => {
+                module.size = metadata.len();
+                module.disk_size = allocated_size(&metadata);
+            }
+            Err(err) => {
+                module.partial = true;
+                errors.push(ScanError::new(path, &err));
+            }
+        }
+        errors.extend(read_package_json(path, &mut module).err());
+        return Measured {
+            module,
+            fingerprint: None,
+            links: Vec::new(),
+            errors,
+        };
+    }
+    if let Some(cached) = cache.and_then(|cache| cache.lookup(&module.path, path)) {
+        let mut module = cached.module;
+        read_last_modified(path, &mut module);
+        return Measured {
+            module,
+            fingerprint: Some(cached.fingerprint),
+            links: cached.links,
+            errors: Vec::new(),
+        };
+    }
+
+    let stats = get_tree_stats(path, true);
+    module.size = stats.size.apparent;
+    module.disk_size = stats.size.disk;
+    module.files_count = Some(stats.files);
+    
+    // Convert the extension counts to a Vec sorted by count
+    let mut file_types: Vec<(String, usize)> = stats.extensions.into_iter().collect();
+    file_types.sort_by_key(|t| std::cmp::Reverse(t.1));
+    module.file_types = Some(file_types);
+
+    let mut errors = stats.errors;
+    module.partial = !errors.is_empty();
+    errors.extend(read_package_json(path, &mut module).err());
+    
+    read_last_modified(path, &mut module);
+
+    // A package with errors is measured again next time, so the errors are
+    // reported again and sizes that are too low are not kept
+    Measured {
+        fingerprint: Some(stats.fingerprint).filter(|_| errors.is_empty()),
+        links: stats.links,
+        module,
+        errors,
+    }
+}
+
+/// Sets when the package directory was last modified, both as a timestamp
+/// and as how long ago, e.g. `3 days ago`.
+fn read_last_modified(path: &Path, module: &mut ModuleInfo) {
+    let Ok(modified) = fs::metadata(path).and_then(|metadata| metadata.modified()) else {
+        return;
+    };
+    module.last_modified = modified.duration_since(UNIX_EPOCH).ok().map(|since| since.as_secs());
+    module.last_updated = modified.elapsed().ok().map(|elapsed| describe_age(elapsed.as_secs()));
+}
+
+fn describe_age(seconds_ago: u64) -> String {
+    if seconds_ago < 60 {
+        format!("{} seconds ago", seconds_ago)
+    } else if seconds_ago < 3600 {
+        format!("{} minutes ago", seconds_ago / 60)
+    } else if seconds_ago < 86400 {
+        format!("{} hours ago", seconds_ago / 3600)
+    } else {
+        format!("{} days ago", seconds_ago / 86400)
+    }
+}
+
+/// Fills in the metadata from the package's `package.json`. A package
+/// without one is fine, one that cannot be read or parsed is an error.
+fn read_package_json(path: &Path, module: &mut ModuleInfo) -> Result<(), ScanError> {
+    let package_json_path = path.join("package.json");
+    if !package_json_path.exists() {
+        return Ok(());
+    }
+    let json_content = fs::read_to_string(&package_json_path).map_err(|err| ScanError::new(&package_json_path, &err))?;
+    let package_json = serde_json::from_str::<PackageJson>(&json_content).map_err(|err| ScanError {
+        path: package_json_path.display().to_string(),
+        message: format!("not a valid package.json: {}", err),
+    })?;
+
+    module.version = package_json.version;
+    module.description = package_json.description;
+    module.license = match (package_json.license, package_json.licenses) {
+        (Some(license), _) => license.to_string(),
+        (None, Some(licenses)) => {
+            let licenses: Vec<String> = licenses.iter().filter_map(License::to_string).collect();
+            Some(licenses.join(" OR ")).filter(|licenses| !licenses.is_empty())
+        }
+        (None, None) => None,
+    };
+    module.author = package_json.author.and_then(|a| a.to_string());
+    module.homepage = package_json.homepage;
+    module.repository = package_json.repository.and_then(|r| r.to_string());
+    
+    // Count dependencies
+    let mut dep_count = 0;
+    if let Some(deps) = &package_json.dependencies {
+        dep_count += deps.len();
+    }
+    if let Some(deps) = &package_json.dev_dependencies {
+        dep_count += deps.len();
+    }
+    if let Some(deps) = &package_json.peer_dependencies {
+        dep_count += deps.len();
+    }
+    if let Some(deps) = &package_json.optional_dependencies {
+        dep_count += deps.len();
+    }
+    
+    module.dependency_count = Some(dep_count);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{self, File};
+    use std::io::Write;
+    use tempfile::tempdir;
+    
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(500), "500 B");
+        assert_eq!(format_size(1024), "1.00 KB");
+        assert_eq!(format_size(1500), "1.46 KB");
+        assert_eq!(format_size(1024 * 1024), "1.00 MB");
+        assert_eq!(format_size(1024 * 1024 * 2 + 1024 * 100), "2.10 MB");
+    }
+    
+    #[test]
+    fn test_get_tree_stats() -> io::Result<()> {
+        // Create a temporary directory
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path();
+        
+        // Create a file with known content
+        let file_path = temp_path.join("test_file.txt");
+        let content = "Hello, world!";
+        let mut file = File::create(&file_path)?;
+        file.write_all(content.as_bytes())?;
+        
+        // Create a subdirectory with a file
+        let subdir_path = temp_path.join("subdir");
+        fs::create_dir(&subdir_path)?;
+        let subfile_path = subdir_path.join("subfile.txt");
+        let subcontent = "This is a test file in a subdirectory";
+        let mut subfile = File::create(&subfile_path)?;
+        subfile.write_all(subcontent.as_bytes())?;
+        
+        // Expected size is the sum of both file contents
+        let expected_size = (content.len() + subcontent.len()) as u64;
+        let stats = get_tree_stats(temp_path, false);
+        let actual_size = stats.size;
+        
+        assert_eq!(actual_size.apparent, expected_size);
+        // Disk usage is counted in whole blocks, directories included
+        if cfg!(unix) {
+            assert!(actual_size.disk > 0);
+            assert_eq!(actual_size.disk % 512, 0);
+        }
+        // Files are counted in the same walk
+        assert_eq!(stats.files, 2);
+        assert_eq!(stats.extensions.get("txt"), Some(&2));
+        Ok(())
+    }
+    
+    #[test]
+    fn test_scan_modules_dir() -> io::Result<()> {
+        // Create a mock node_modules directory structure
+        let temp_dir = tempdir()?;
+        let mock_node_modules = temp_dir.path();
+        
+        // Create a few mock modules with different sizes
+        let modules = vec![
+            ("small-module", 100),
+            ("medium-module", 500),
+            ("large-module", 1000)
+        ];
+        
+        for (name, size) in &modules {
+            let module_path = mock_node_modules.join(name);
+            fs::create_dir(&module_path)?;
+            let file_path = module_path.join("index.js");
+            let content = "a".repeat(*size);
+            let mut file = File::create(file_path)?;
+            file.write_all(content.as_bytes())?;
+        }
+        
+        // Scan the mock node_modules directory
+        let result = scan_modules_dir(mock_node_modules, &ScanOptions::default())?.modules;
+        
+        // Check that we have all expected modules
+        assert_eq!(result.len(), modules.len());
+        
+        // Check that they're sorted by size (largest first)
+        assert_eq!(result[0].name, "large-module");
+        assert_eq!(result[1].name, "medium-module");
+        assert_eq!(result[2].name, "small-module");
+        
+        // Check actual sizes
+        assert_eq!(result[0].size, 1000);
+        assert_eq!(result[1].size, 500);
+        assert_eq!(result[2].size, 100);
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_module_info_with_package_json() -> io::Result<()> {
+        // Create a temporary directory
+        let temp_dir = tempdir()?;
+        let mock_node_modules = temp_dir.path();
+        
+        // Create a module with package.json
+        let module_name = "test-module";
+        let module_path = mock_node_modules.join(module_name);
+        fs::create_dir(&module_path)?;
+        
+        // Create some files to count
+        fs::create_dir_all(module_path.join("src"))?;
+        let js_file_path = module_path.join("src/index.js");
+        let js_content = "console.log('Hello, World!');";
+        let mut js_file = File::create(js_file_path)?;
+        js_file.write_all(js_content.as_bytes())?;
+        
+        let ts_file_path = module_path.join("src/types.ts");
+        let ts_content = "export type Test = { name: string; };";
+        let mut ts_file = File::create(ts_file_path)?;
+        ts_file.write_all(ts_content.as_bytes())?;
+        
+        // Create a package.json with test data
+        let package_json_path = module_path.join("package.json");
+        let package_json_content = r#"{
+            "name": "test-module",
+            "version": "1.0.0",
+            "description": "A test module",
+            "author": "Test Author",
+            "license": "MIT",
+            "homepage": "https://example.com",
+            "repository": {
+                "type": "git",
+                "url": "https://github.com/test/test-module"
+            },
+            "dependencies": {
+                "dep1": "^1.0.0",
+                "dep2": "^2.0.0"
+            },
+            "devDependencies": {
+                "devdep1": "^1.0.0"
+            }
+        }"#;
+        let mut package_json_file = File::create(package_json_path)?;
+        package_json_file.write_all(package_json_content.as_bytes())?;
+        
+        // Scan the mock node_modules directory
+        let result = scan_modules_dir(mock_node_modules, &ScanOptions::default())?.modules;
+        
+        // Check that we have our module
+        assert_eq!(result.len(), 1);
+        let module = &result[0];
+        
+        // Check basic info
+        assert_eq!(module.name, module_name);
+        
+        // Check package.json derived info
+        assert_eq!(module.version, Some("1.0.0".to_string()));
+        assert_eq!(module.description, Some("A test module".to_string()));
+        assert_eq!(module.author, Some("Test Author".to_string()));
+        assert_eq!(module.license, Some("MIT".to_string()));
+        assert_eq!(module.homepage, Some("https://example.com".to_string()));
+        assert_eq!(module.repository, Some("https://github.com/test/test-module".to_string()));
+        
+        // Check dependency count (2 deps + 1 dev dep = 3)
+        assert_eq!(module.dependency_count, Some(3));
+        
+        // Check files count (package.json + 2 source files = 3)
+        assert_eq!(module.files_count, Some(3));
+        
+        // Check file types
+        if let Some(file_types) = &module.file_types {
+            // Convert to HashMap for easier checking
+            let file_types_map: HashMap<_, _> = file_types.iter().cloned().collect();
+            
+            // Should have .js and .ts files
+            assert_eq!(file_types_map.get("js"), Some(&1));
+            assert_eq!(file_types_map.get("ts"), Some(&1));
+            assert_eq!(file_types_map.get("json"), Some(&1));
+        } else {
+            panic!("No file types found");
+        }
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_scan_scoped_packages() -> io::Result<()> {
+        let temp_dir = tempdir()?;
+        let mock_node_modules = temp_dir.path();
+        
+        for (name, size) in [("@babel/core", 300), ("@babel/parser", 200), ("@types/node", 50), ("lodash", 400)] {
+            let module_path = mock_node_modules.join(name);
+            fs::create_dir_all(&module_path)?;
+            let mut file = File::create(module_path.join("index.js"))?;
+            file.write_all("a".repeat(size).as_bytes())?;
+        }
+        
+        let result = scan_modules_dir(mock_node_modules, &ScanOptions::default())?.modules;
+        let names: Vec<&str> = result.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, vec!["lodash", "@babel/core", "@babel/parser", "@types/node"]);
+        assert_eq!(result[1].scope(), Some("@babel"));
+        assert_eq!(result[0].scope(), None);
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_retain_modules() -> io::Result<()> {
+        let temp_dir = tempdir()?;
+        let root = temp_dir.path();
+        for (path, size) in [
+            ("a", 100),
+            ("a/node_modules/b", 10),
+            ("a/node_modules/b/node_modules/c", 1),
+            ("a/node_modules/d", 1000),
+        ] {
+            fs::create_dir_all(root.join(path))?;
+            File::create(root.join(path).join("index.js"))?.write_all(&vec![b'a'; size])?;
+        }
+
+        let mut modules = scan_modules_dir(root, &ScanOptions::default())?.modules;
+        let a = modules.iter().find(|m| m.name == "a").unwrap();
+        assert_eq!(a.nested_size, 1011);
+
+        // Dropping b keeps c counted in a, whose node_modules it still lives in
+        retain_modules(&mut modules, |m| m.name != "b" && m.name != "d");
+        let names: Vec<&str> = modules.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, ["a", "c"]);
+        assert_eq!(modules[0].nested_size, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_nested_node_modules() -> io::Result<()> {
+        let temp_dir = tempdir()?;
+        let mock_node_modules = temp_dir.path();
+        
+        for (path, size) in [
+            ("foo", 100),
+            ("foo/node_modules/bar", 40),
+            ("foo/node_modules/bar/node_modules/@s/baz", 10),
+            ("bar", 60),
+        ] {
+            let module_path = mock_node_modules.join(path);
+            fs::create_dir_all(&module_path)?;
+            let mut file = File::create(module_path.join("index.js"))?;
+            file.write_all("a".repeat(size).as_bytes())?;
+        }
+        
+        let result = scan_modules_dir(mock_node_modules, &ScanOptions::default())?.modules;
+        assert_eq!(result.len(), 4);
+        
+        let find = |path: &str| result.iter().find(|m| m.path == path).unwrap();
+        
+        let foo = find("node_modules/foo");
+        assert_eq!(foo.size, 100);
+        assert_eq!(foo.nested_size, 50);
+        assert_eq!(foo.total_size(), 150);
+        assert_eq!(foo.depth, 0);
+        assert_eq!(foo.parent, None);
+        
+        let nested_bar = find("node_modules/foo/node_modules/bar");
+        assert_eq!(nested_bar.name, "bar");
+        assert_eq!(nested_bar.size, 40);
+        assert_eq!(nested_bar.nested_size, 10);
+        assert_eq!(nested_bar.depth, 1);
+        assert_eq!(nested_bar.parent_name(), Some("foo"));
+        assert_eq!(nested_bar.files_count, Some(1));
+        
+        let baz = find("node_modules/foo/node_modules/bar/node_modules/@s/baz");
+        assert_eq!(baz.name, "@s/baz");
+        assert_eq!(baz.depth, 2);
+        assert_eq!(baz.parent.as_deref(), Some("node_modules/foo/node_modules/bar"));
+        
+        assert_eq!(find("node_modules/bar").parent_name(), None);
+        
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_scan_pnpm_layout() -> io::Result<()> {
+        use std::os::unix::fs::symlink;
+
+        let temp_dir = tempdir()?;
+        let node_modules = temp_dir.path();
+        let store = node_modules.join(".pnpm");
+
+        for (entry, name, size) in [("foo@1.0.0", "foo", 100), ("@s+bar@2.0.0", "@s/bar", 50)] {
+            let package = store.join(entry).join("node_modules").join(name);
+            fs::create_dir_all(&package)?;
+            File::create(package.join("index.js"))?.write_all(&vec![b'a'; size])?;
+        }
+        // foo depends on @s/bar through a sibling symlink inside the store
+        fs::create_dir_all(store.join("foo@1.0.0/node_modules/@s"))?;
+        symlink(
+            store.join("@s+bar@2.0.0/node_modules/@s/bar"),
+            store.join("foo@1.0.0/node_modules/@s/bar"),
+        )?;
+        fs::create_dir_all(store.join("node_modules"))?;
+        symlink(store.join("foo@1.0.0/node_modules/foo"), store.join("node_modules/foo"))?;
+        // Top-level entries are symlinks into the store
+        symlink(store.join("foo@1.0.0/node_modules/foo"), node_modules.join("foo"))?;
+        File::create(store.join("lock.yaml"))?.write_all(b"lockfileVersion: '6.0'")?;
+
+        assert!(is_pnpm_layout(node_modules));
+        let result = scan_modules_dir(node_modules, &ScanOptions::default())?.modules;
+        let summary: Vec<(&str, &str, u64)> =
+            result.iter().map(|m| (m.name.as_str(), m.path.as_str(), m.size)).collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("foo", "node_modules/.pnpm/foo@1.0.0/node_modules/foo", 100),
+                ("@s/bar", "node_modules/.pnpm/@s+bar@2.0.0/node_modules/@s/bar", 50),
+            ]
+        );
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hard_links_counted_once() -> io::Result<()> {
+        let temp_dir = tempdir()?;
+        let node_modules = temp_dir.path();
+        for name in ["a", "b", "c"] {
+            fs::create_dir_all(node_modules.join(name))?;
+        }
+        File::create(node_modules.join("a/index.js"))?.write_all(&[b'a'; 300])?;
+        fs::hard_link(node_modules.join("a/index.js"), node_modules.join("b/index.js"))?;
+        fs::hard_link(node_modules.join("a/index.js"), node_modules.join("c/copy.js"))?;
+        File::create(node_modules.join("c/own.js"))?.write_all(&[b'c'; 20])?;
+
+        let result = scan_modules_dir(node_modules, &ScanOptions::default())?.modules;
+        // The first package listed owns the file, the others share it
+        assert_eq!(result.iter().map(|m| m.size).sum::<u64>(), 320);
+        assert_eq!(result.iter().map(|m| m.shared_size).sum::<u64>(), 600);
+        let c = result.iter().find(|m| m.name == "c").unwrap();
+        assert_eq!(c.size + c.shared_size, 320);
+        assert_eq!(c.files_count, Some(2));
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hard_link_owners_do_not_depend_on_threads() -> io::Result<()> {
+        let temp_dir = tempdir()?;
+        let node_modules = temp_dir.path();
+        File::create(temp_dir.path().join("store.js"))?.write_all(&[b's'; 500])?;
+        for i in 0..12 {
+            let package = node_modules.join(format!("pkg-{}", i));
+            fs::create_dir_all(&package)?;
+            fs::hard_link(node_modules.join("store.js"), package.join("index.js"))?;
+            File::create(package.join("own.js"))?.write_all(&vec![b'o'; i * 10])?;
+        }
+        fs::remove_file(node_modules.join("store.js"))?;
+
+        let scan = |threads| -> io::Result<Vec<(String, u64, u64)>> {
+            let options = ScanOptions {
+                threads,
+                ..ScanOptions::default()
+            };
+            let mut modules = scan_modules_dir(node_modules, &options)?.modules;
+            modules.sort_by(|a, b| a.path.cmp(&b.path));
+            Ok(modules.into_iter().map(|m| (m.path, m.size, m.shared_size)).collect())
+        };
+        let single = scan(1)?;
+        assert_eq!(single.iter().filter(|(_, _, shared)| *shared == 0).count(), 1);
+        for _ in 0..5 {
+            assert_eq!(scan(8)?, single);
+        }
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_policies() -> io::Result<()> {
+        use std::os::unix::fs::symlink;
+
+        let temp_dir = tempdir()?;
+        let node_modules = temp_dir.path().join("app/node_modules");
+        let workspace = temp_dir.path().join("packages/lib");
+        fs::create_dir_all(node_modules.join("dep"))?;
+        fs::create_dir_all(workspace.join("node_modules"))?;
+        File::create(node_modules.join("dep/index.js"))?.write_all(&[b'a'; 100])?;
+        File::create(workspace.join("index.js"))?.write_all(&[b'a'; 40])?;
+        File::create(workspace.join("package.json"))?.write_all(br#"{"version": "0.1.0"}"#)?;
+        // Linked from outside, twice, with a cycle back to itself and a link into node_modules
+        symlink(&workspace, node_modules.join("lib"))?;
+        symlink(&workspace, node_modules.join("lib-alias"))?;
+        symlink(&workspace, workspace.join("node_modules/lib"))?;
+        symlink(node_modules.join("dep"), node_modules.join("dep-alias"))?;
+
+        let scan = |symlinks| {
+            let options = ScanOptions {
+                symlinks,
+                threads: 2,
+                progress: None,
+                cache_dir: None,
+            };
+            let mut modules = scan_modules_dir(&node_modules, &options).unwrap().modules;
+            modules.sort_by(|a, b| a.path.cmp(&b.path));
+            modules
+        };
+
+        let modules = scan(SymlinkPolicy::Skip);
+        assert_eq!(modules.len(), 1);
+        assert_eq!(modules[0].name, "dep");
+
+        let modules = scan(SymlinkPolicy::Follow);
+        let measured: Vec<&ModuleInfo> = modules.iter().filter(|m| m.size >= 40).collect();
+        assert_eq!(modules.len(), 4);
+        assert_eq!(measured.len(), 2);
+        let lib = measured.iter().find(|m| m.name != "dep").unwrap();
+        assert_eq!(lib.version.as_deref(), Some("0.1.0"));
+        assert!(lib.link_target.as_deref().is_some_and(|t| t.ends_with("packages/lib")));
+        // The other link and the cycle are listed, but only with the size of the link
+        let link_only = modules.iter().filter(|m| m.size < 40 && m.link_target.is_some()).count();
+        assert_eq!(link_only, 2);
+
+        let modules = scan(SymlinkPolicy::Link);
+        assert_eq!(modules.len(), 3);
+        assert!(modules.iter().filter(|m| m.name != "dep").all(|m| m.size < 40 && m.link_target.is_some()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_package_json_shapes() -> io::Result<()> {
+        let temp_dir = tempdir()?;
+        let node_modules = temp_dir.path();
+        let manifests = [
+            ("objects", r#"{"author": {"name": "Ann", "email": "ann@example.com", "url": "https://ann.dev"},
+                "licenses": [{"type": "MIT"}, {"type": "Apache-2.0"}],
+                "repository": {"type": "git", "url": "gitlab:ann/objects"}}"#),
+            ("shorthand", r#"{"author": "Bob <bob@example.com>", "license": {"type": "ISC"},
+                "repository": "bob/shorthand", "publishConfig": {"provenance": true}}"#),
+            ("broken", r#"{"version": "1.0.0",}"#),
+        ];
+        for (name, manifest) in manifests {
+            fs::create_dir(node_modules.join(name))?;
+            File::create(node_modules.join(name).join("package.json"))?.write_all(manifest.as_bytes())?;
+        }
+
+        let output = scan_modules_dir(node_modules, &ScanOptions::default())?;
+        let module = |name| output.modules.iter().find(|m| m.name == name).unwrap();
+        assert_eq!(module("objects").author.as_deref(), Some("Ann <ann@example.com> (https://ann.dev)"));
+        assert_eq!(module("objects").license.as_deref(), Some("MIT OR Apache-2.0"));
+        assert_eq!(module("objects").repository.as_deref(), Some("https://gitlab.com/ann/objects"));
+        assert_eq!(module("shorthand").author.as_deref(), Some("Bob <bob@example.com>"));
+        assert_eq!(module("shorthand").license.as_deref(), Some("ISC"));
+        assert_eq!(module("shorthand").repository.as_deref(), Some("https://github.com/bob/shorthand"));
+        assert_eq!(expand_repository("github:user/repo"), "https://github.com/user/repo");
+        assert_eq!(expand_repository("git+ssh://git@github.com/user/repo.git"), "git+ssh://git@github.com/user/repo.git");
+
+        // A broken manifest is reported, the package is still measured
+        assert_eq!(module("broken").version, None);
+        assert!(!module("broken").partial);
+        assert_eq!(output.errors.len(), 1);
+        assert!(output.errors[0].path.ends_with("package.json"));
+        assert!(output.errors[0].message.starts_with("not a valid package.json"));
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unreadable_directories_do_not_stop_the_scan() -> io::Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = tempdir()?;
+        let node_modules = temp_dir.path();
+        let locked = node_modules.join("a/locked");
+        fs::create_dir_all(&locked)?;
+        fs::create_dir_all(node_modules.join("b"))?;
+        File::create(node_modules.join("a/index.js"))?.write_all(&[b'a'; 100])?;
+        File::create(locked.join("index.js"))?.write_all(&[b'a'; 50])?;
+        File::create(node_modules.join("b/index.js"))?.write_all(&[b'a'; 10])?;
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o000))?;
+        // Root reads the directory anyway, there is nothing to test then
+        let unreadable = fs::read_dir(&locked).is_err();
+
+        let result = scan_modules_dir(node_modules, &ScanOptions::default());
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o755))?;
+        let output = result?;
+        assert_eq!(output.modules.len(), 2);
+        let a = output.modules.iter().find(|m| m.name == "a").unwrap();
+        let b = output.modules.iter().find(|m| m.name == "b").unwrap();
+        assert!(!b.partial);
+        assert_eq!(b.size, 10);
+        if unreadable {
+            assert!(a.partial);
+            assert_eq!(a.size, 100);
+            assert_eq!(output.errors.len(), 1);
+            assert_eq!(output.errors[0].path, locked.display().to_string());
+        } else {
+            assert!(!a.partial);
+            assert!(output.errors.is_empty());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_dot_directories_are_not_packages() -> io::Result<()> {
+        let temp_dir = tempdir()?;
+        let node_modules = temp_dir.path();
+        for dir in [".bin", ".cache/tool", "a/node_modules/.bin", "a/node_modules/b", "@s/.tmp", "@s/c"] {
+            fs::create_dir_all(node_modules.join(dir))?;
+        }
+        File::create(node_modules.join(".bin/tool"))?.write_all(b"#!/bin/sh")?;
+        File::create(node_modules.join("a/node_modules/.bin/tool"))?.write_all(b"#!/bin/sh")?;
+
+        let output = scan_modules_dir(node_modules, &ScanOptions::default())?;
+        let mut names: Vec<&str> = output.modules.iter().map(|m| m.name.as_str()).collect();
+        names.sort();
+        assert_eq!(names, ["@s/c", "a", "b"]);
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_broken_package_links_are_reported() -> io::Result<()> {
+        use std::os::unix::fs::symlink;
+
+        let temp_dir = tempdir()?;
+        let node_modules = temp_dir.path();
+        fs::create_dir_all(node_modules.join("a"))?;
+        fs::create_dir_all(node_modules.join("@s/ok"))?;
+        symlink("../missing", node_modules.join("gone"))?;
+        symlink("../../missing", node_modules.join("@s/gone"))?;
+        fs::write(node_modules.join(".package-lock.json"), "{}")?;
+
+        let output = scan_modules_dir(node_modules, &ScanOptions::default())?;
+        let mut names: Vec<&str> = output.modules.iter().map(|m| m.name.as_str()).collect();
+        names.sort();
+        assert_eq!(names, ["@s/ok", "a"]);
+        let mut errors: Vec<String> = output.errors.iter().map(|e| e.path.clone()).collect();
+        errors.sort();
+        let expected = [node_modules.join("@s/gone"), node_modules.join("gone")];
+        assert_eq!(errors, expected.map(|path| path.display().to_string()));
+        Ok(())
+    }
+}
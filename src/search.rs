@@ -0,0 +1,245 @@
+use regex::{Regex, RegexBuilder};
+
+use crate::budget::parse_size;
+use crate::scanner::ModuleInfo;
+
+/// A search of the module list: whitespace separated terms that all have to
+/// match. A term is one of
+/// - `text`, a case-insensitive substring of the name
+/// - `~text`, a fuzzy match: the characters appear in the name in this order
+/// - `/pattern/`, a regular expression matched against the name
+/// - `field:glob` for `name`, `version`, `license`, `author` and `type`, with
+///   `*` and `?` wildcards, e.g. `license:GPL*`
+/// - `dev:true` or `dev:false`
+/// - a comparison of `size`, `disk`, `files` or `deps` using `<`, `<=`, `=`,
+///   `>=` or `>`, e.g. `size>5MB` or `deps=0`
+#[derive(Debug)]
+pub struct Query {
+    terms: Vec<Term>,
+}
+
+#[derive(Debug)]
+enum Term {
+    Text(String),
+    Fuzzy(String),
+    Regex(Regex),
+    Field(TextField, Regex),
+    Dev(bool),
+    Compare(Measure, Comparison, u64),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum TextField {
+    Name,
+    Version,
+    License,
+    Author,
+    Kind,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Measure {
+    Size,
+    Disk,
+    Files,
+    Deps,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Comparison {
+    Less,
+    LessOrEqual,
+    Equal,
+    GreaterOrEqual,
+    Greater,
+}
+
+impl Query {
+    pub fn parse(text: &str) -> Result<Query, String> {
+        let terms = text.split_whitespace().map(parse_term).collect::<Result<_, _>>()?;
+        Ok(Query { terms })
+    }
+
+    pub fn matches(&self, module: &ModuleInfo) -> bool {
+        self.terms.iter().all(|term| term.matches(module))
+    }
+}
+
+fn parse_term(term: &str) -> Result<Term, String> {
+    if let Some(pattern) = term.strip_prefix('/').and_then(|rest| rest.strip_suffix('/')) {
+        let regex = Regex::new(pattern).map_err(|err| format!("invalid pattern '{}': {}", pattern, err))?;
+        return Ok(Term::Regex(regex));
+    }
+    if let Some(text) = term.strip_prefix('~') {
+        return Ok(Term::Fuzzy(text.to_lowercase()));
+    }
+    if let Some((field, value)) = term.split_once(':') {
+        let field = match field.to_ascii_lowercase().as_str() {
+            "name" => TextField::Name,
+            "version" => TextField::Version,
+            "license" => TextField::License,
+            "author" => TextField::Author,
+            "type" => TextField::Kind,
+            "dev" => {
+                return match value.to_ascii_lowercase().as_str() {
+                    "true" | "yes" => Ok(Term::Dev(true)),
+                    "false" | "no" => Ok(Term::Dev(false)),
+                    _ => Err(format!("expected dev:true or dev:false, got '{}'", term)),
+                };
+            }
+            _ => return Err(format!("unknown filter '{}:'", field)),
+        };
+        return Ok(Term::Field(field, glob(value)?));
+    }
+    if let Some(split) = term.find(['<', '>', '=']) {
+        let (measure, rest) = term.split_at(split);
+        let measure = match measure.to_ascii_lowercase().as_str() {
+            "size" => Measure::Size,
+            "disk" => Measure::Disk,
+            "files" => Measure::Files,
+            "deps" => Measure::Deps,
+            _ => return Err(format!("cannot compare '{}', only size, disk, files and deps", measure)),
+        };
+        let (comparison, value) = if let Some(value) = rest.strip_prefix("<=") {
+            (Comparison::LessOrEqual, value)
+        } else if let Some(value) = rest.strip_prefix(">=") {
+            (Comparison::GreaterOrEqual, value)
+        } else if let Some(value) = rest.strip_prefix('<') {
+            (Comparison::Less, value)
+        } else if let Some(value) = rest.strip_prefix('>') {
+            (Comparison::Greater, value)
+        } else {
+            (Comparison::Equal, &rest[1..])
+        };
+        let value = match measure {
+            Measure::Size | Measure::Disk => parse_size(value)?,
+            Measure::Files | Measure::Deps => value.parse().map_err(|_| format!("invalid count '{}'", value))?,
+        };
+        return Ok(Term::Compare(measure, comparison, value));
+    }
+    Ok(Term::Text(term.to_lowercase()))
+}
+
+/// Turns a glob with `*` and `?` into a case-insensitive regex matching the
+/// whole value.
+fn glob(pattern: &str) -> Result<Regex, String> {
+    let mut regex = String::from("^");
+    for c in pattern.chars() {
+        match c {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+    RegexBuilder::new(&regex)
+        .case_insensitive(true)
+        .build()
+        .map_err(|err| format!("invalid pattern '{}': {}", pattern, err))
+}
+
+impl Term {
+    fn matches(&self, module: &ModuleInfo) -> bool {
+        match self {
+            Term::Text(text) => module.name.to_lowercase().contains(text),
+            Term::Fuzzy(text) => {
+                let mut name = module.name.chars().flat_map(char::to_lowercase);
+                text.chars().all(|c| name.any(|n| n == c))
+            }
+            Term::Regex(regex) => regex.is_match(&module.name),
+            Term::Field(field, glob) => field.value(module).is_some_and(|value| glob.is_match(value)),
+            Term::Dev(dev) => module.is_dev_dependency == *dev,
+            Term::Compare(measure, comparison, value) => {
+                measure.value(module).is_some_and(|actual| comparison.holds(actual, *value))
+            }
+        }
+    }
+}
+
+impl TextField {
+    fn value(self, module: &ModuleInfo) -> Option<&str> {
+        match self {
+            TextField::Name => Some(&module.name),
+            TextField::Version => module.version.as_deref(),
+            TextField::License => module.license.as_deref(),
+            TextField::Author => module.author.as_deref(),
+            TextField::Kind => module.dependency_kind.map(|kind| kind.label()),
+        }
+    }
+}
+
+impl Measure {
+    fn value(self, module: &ModuleInfo) -> Option<u64> {
+        match self {
+            Measure::Size => Some(module.size),
+            Measure::Disk => Some(module.disk_size),
+            Measure::Files => module.files_count.map(|count| count as u64),
+            Measure::Deps => module.dependency_count.map(|count| count as u64),
+        }
+    }
+}
+
+impl Comparison {
+    fn holds(self, actual: u64, value: u64) -> bool {
+        match self {
+            Comparison::Less => actual < value,
+            Comparison::LessOrEqual => actual <= value,
+            Comparison::Equal => actual == value,
+            Comparison::GreaterOrEqual => actual >= value,
+            Comparison::Greater => actual > value,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lockfile::DependencyKind;
+
+    fn module(name: &str, size: u64, license: &str) -> ModuleInfo {
+        ModuleInfo {
+            name: name.to_string(),
+            size,
+            license: Some(license.to_string()),
+            dependency_count: Some(2),
+            ..ModuleInfo::default()
+        }
+    }
+
+    fn matching(query: &str, modules: &[ModuleInfo]) -> Vec<String> {
+        let query = Query::parse(query).unwrap();
+        modules.iter().filter(|m| query.matches(m)).map(|m| m.name.clone()).collect()
+    }
+
+    #[test]
+    fn test_queries() {
+        let mut modules = vec![
+            module("react-dom", 6 * 1024 * 1024, "MIT"),
+            module("readline", 1024, "GPL-3.0"),
+            module("@types/react", 2048, "MIT"),
+        ];
+        modules[1].is_dev_dependency = true;
+        modules[1].dependency_kind = Some(DependencyKind::Dev);
+
+        assert_eq!(matching("", &modules).len(), 3);
+        assert_eq!(matching("REACT", &modules), ["react-dom", "@types/react"]);
+        assert_eq!(matching("~rdom", &modules), ["react-dom"]);
+        assert_eq!(matching("/^re.*e$/", &modules), ["readline"]);
+        assert_eq!(matching("license:gpl*", &modules), ["readline"]);
+        assert_eq!(matching("license:MIT size>5MB", &modules), ["react-dom"]);
+        assert_eq!(matching("size<=2KB deps=2", &modules), ["readline", "@types/react"]);
+        assert_eq!(matching("dev:true", &modules), ["readline"]);
+        assert_eq!(matching("type:dev", &modules), ["readline"]);
+        assert_eq!(matching("files>0", &modules), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_invalid_queries() {
+        assert!(Query::parse("/[/").is_err());
+        assert!(Query::parse("color:red").is_err());
+        assert!(Query::parse("size>5XB").is_err());
+        assert!(Query::parse("files>many").is_err());
+        assert!(Query::parse("dev:maybe").is_err());
+        assert!(Query::parse("weight>5").is_err());
+    }
+}
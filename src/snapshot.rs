@@ -0,0 +1,306 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs, io,
+    path::Path,
+};
+
+use crate::report::REPORT_SCHEMA_VERSION;
+use crate::cli::SizeMetric;
+use crate::scanner::{format_size, ModuleInfo};
+
+/// A previously saved scan. Snapshots are the JSON reports written by
+/// `--format json`, so any JSON report can be compared as well.
+#[derive(Debug, Deserialize)]
+pub struct Snapshot {
+    pub schema_version: u32,
+    pub scan_root: String,
+    pub scanned_at: String,
+    /// Missing in snapshots written before production-only scans existed
+    #[serde(default)]
+    pub production_only: bool,
+    pub modules: Vec<ModuleInfo>,
+}
+
+impl Snapshot {
+    pub fn from_file(path: &Path) -> io::Result<Snapshot> {
+        let content = fs::read_to_string(path)?;
+        let snapshot: Snapshot = serde_json::from_str(&content).map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid snapshot {}: {}", path.display(), err),
+            )
+        })?;
+        if snapshot.schema_version > REPORT_SCHEMA_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "snapshot {} uses schema version {}, this version of node-size reads up to {}",
+                    path.display(),
+                    snapshot.schema_version,
+                    REPORT_SCHEMA_VERSION
+                ),
+            ));
+        }
+        Ok(snapshot)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    VersionChanged,
+    SizeChanged,
+}
+
+/// Change of one package between two scans. Installed copies of a package are
+/// combined, so hoisting changes do not show up as add/remove pairs.
+#[derive(Debug, Clone, Serialize)]
+pub struct DiffEntry {
+    pub name: String,
+    pub kind: ChangeKind,
+    pub old_versions: Vec<String>,
+    pub new_versions: Vec<String>,
+    pub old_size: u64,
+    pub new_size: u64,
+    pub delta: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ScanDiff {
+    pub schema_version: u32,
+    /// Where and when the older scan was taken, when it came from a snapshot
+    pub old_scan: Option<ScanOrigin>,
+    /// Where and when the newer scan was taken, when it came from a snapshot
+    pub new_scan: Option<ScanOrigin>,
+    pub old_total: u64,
+    pub new_total: u64,
+    pub delta: i64,
+    pub entries: Vec<DiffEntry>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ScanOrigin {
+    pub scan_root: String,
+    pub scanned_at: String,
+}
+
+impl Snapshot {
+    pub fn origin(&self) -> ScanOrigin {
+        ScanOrigin {
+            scan_root: self.scan_root.clone(),
+            scanned_at: self.scanned_at.clone(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct PackageSummary {
+    size: u64,
+    versions: BTreeSet<String>,
+}
+
+fn summarize(modules: &[ModuleInfo], metric: SizeMetric) -> HashMap<&str, PackageSummary> {
+    let mut packages: HashMap<&str, PackageSummary> = HashMap::new();
+    for module in modules {
+        let summary = packages.entry(&module.name).or_default();
+        summary.size += module.size_by(metric);
+        if let Some(version) = &module.version {
+            summary.versions.insert(version.clone());
+        }
+    }
+    packages
+}
+
+/// Compares two scans. Unchanged packages are left out and the entries are
+/// sorted by absolute size delta, largest first. Sizes are compared by `metric`.
+pub fn diff_scans(old: &[ModuleInfo], new: &[ModuleInfo], metric: SizeMetric) -> ScanDiff {
+    let old_packages = summarize(old, metric);
+    let new_packages = summarize(new, metric);
+    let empty = PackageSummary::default();
+
+    let names: BTreeSet<&str> = old_packages.keys().chain(new_packages.keys()).copied().collect();
+    let mut entries: Vec<DiffEntry> = names
+        .into_iter()
+        .filter_map(|name| {
+            let before = old_packages.get(name);
+            let after = new_packages.get(name);
+            let kind = match (before, after) {
+                (None, Some(_)) => ChangeKind::Added,
+                (Some(_), None) => ChangeKind::Removed,
+                (Some(b), Some(a)) if b.versions != a.versions => ChangeKind::VersionChanged,
+                (Some(b), Some(a)) if b.size != a.size => ChangeKind::SizeChanged,
+                _ => return None,
+            };
+            let before = before.unwrap_or(&empty);
+            let after = after.unwrap_or(&empty);
+            Some(DiffEntry {
+                name: name.to_string(),
+                kind,
+                old_versions: before.versions.iter().cloned().collect(),
+                new_versions: after.versions.iter().cloned().collect(),
+                old_size: before.size,
+                new_size: after.size,
+                delta: after.size as i64 - before.size as i64,
+            })
+        })
+        .collect();
+    entries.sort_by_key(|e| std::cmp::Reverse(e.delta.unsigned_abs()));
+
+    let old_total: u64 = old.iter().map(|m| m.size_by(metric)).sum();
+    let new_total: u64 = new.iter().map(|m| m.size_by(metric)).sum();
+    ScanDiff {
+        schema_version: REPORT_SCHEMA_VERSION,
+        old_scan: None,
+        new_scan: None,
+        old_total,
+        new_total,
+        delta: new_total as i64 - old_total as i64,
+        entries,
+    }
+}
+
+/// Formats a size difference with an explicit sign.
+pub fn format_size_delta(delta: i64) -> String {
+    let sign = if delta < 0 { "-" } else { "+" };
+    format!("{}{}", sign, format_size(delta.unsigned_abs()))
+}
+
+impl DiffEntry {
+    /// Short description such as `added 1.2.0` or `1.0.0 -> 2.0.0`.
+    pub fn describe(&self) -> String {
+        let versions = |v: &[String]| if v.is_empty() { "?".to_string() } else { v.join(", ") };
+        match self.kind {
+            ChangeKind::Added => format!("added {}", versions(&self.new_versions)),
+            ChangeKind::Removed => format!("removed {}", versions(&self.old_versions)),
+            ChangeKind::VersionChanged => {
+                format!("{} -> {}", versions(&self.old_versions), versions(&self.new_versions))
+            }
+            ChangeKind::SizeChanged => format!("size changed at {}", versions(&self.new_versions)),
+        }
+    }
+}
+
+pub fn write_diff_text(diff: &ScanDiff, out: &mut impl io::Write) -> io::Result<()> {
+    let describe = |origin: &Option<ScanOrigin>| match origin {
+        Some(origin) => format!("{} ({})", origin.scan_root, origin.scanned_at),
+        None => "live scan".to_string(),
+    };
+    if diff.old_scan.is_some() || diff.new_scan.is_some() {
+        writeln!(out, "Old: {}", describe(&diff.old_scan))?;
+        writeln!(out, "New: {}", describe(&diff.new_scan))?;
+    }
+    writeln!(
+        out,
+        "Total: {} -> {} ({})",
+        format_size(diff.old_total),
+        format_size(diff.new_total),
+        format_size_delta(diff.delta)
+    )?;
+    if diff.entries.is_empty() {
+        writeln!(out, "No package changes")?;
+        return Ok(());
+    }
+
+    let name_width = diff.entries.iter().map(|e| e.name.len()).max().unwrap_or(0);
+    writeln!(out)?;
+    for entry in &diff.entries {
+        writeln!(
+            out,
+            "{:>12}  {:<name_width$}  {}",
+            format_size_delta(entry.delta),
+            entry.name,
+            entry.describe()
+        )?;
+    }
+    Ok(())
+}
+
+pub fn write_diff_json(diff: &ScanDiff, out: &mut impl io::Write) -> io::Result<()> {
+    serde_json::to_writer_pretty(&mut *out, diff)?;
+    writeln!(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::{Column, OutputFormat};
+    use crate::report::{write_report, ScanReport};
+    use crate::scanner::{scan_modules_dir, ScanOptions};
+    use std::fs::File;
+    use std::io::Write;
+    use tempfile::tempdir;
+
+    fn write_module(root: &Path, name: &str, version: &str, size: usize) -> io::Result<()> {
+        let module_path = root.join(name);
+        fs::create_dir_all(&module_path)?;
+        File::create(module_path.join("index.js"))?.write_all(&vec![b'a'; size])?;
+        File::create(module_path.join("package.json"))?
+            .write_all(format!(r#"{{"version": "{}"}}"#, version).as_bytes())?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_snapshot_round_trip_and_diff() -> io::Result<()> {
+        let temp_dir = tempdir()?;
+        let root = temp_dir.path().join("node_modules");
+        write_module(&root, "kept", "1.0.0", 100)?;
+        write_module(&root, "upgraded", "1.0.0", 100)?;
+        write_module(&root, "removed", "1.0.0", 50)?;
+        write_module(&root, "grown", "1.0.0", 10)?;
+
+        let snapshot_path = temp_dir.path().join("snapshot.json");
+        let before = scan_modules_dir(&root, &ScanOptions::default())?.modules;
+        let report = ScanReport::new(&before, &root, None);
+        write_report(&report, OutputFormat::Json, &Column::ALL, &mut File::create(&snapshot_path)?)?;
+        let snapshot = Snapshot::from_file(&snapshot_path)?;
+        assert_eq!(snapshot.schema_version, REPORT_SCHEMA_VERSION);
+        assert_eq!(snapshot.modules.len(), 4);
+
+        fs::remove_dir_all(root.join("removed"))?;
+        fs::remove_dir_all(root.join("upgraded"))?;
+        write_module(&root, "upgraded", "2.0.0", 400)?;
+        write_module(&root, "added", "0.1.0", 1000)?;
+        File::create(root.join("grown/extra.js"))?.write_all(&[b'a'; 20])?;
+
+        let after = scan_modules_dir(&root, &ScanOptions::default())?.modules;
+        let diff = diff_scans(&snapshot.modules, &after, SizeMetric::Apparent);
+
+        let summary: Vec<(&str, ChangeKind, i64)> =
+            diff.entries.iter().map(|e| (e.name.as_str(), e.kind, e.delta)).collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("added", ChangeKind::Added, 1020),
+                ("upgraded", ChangeKind::VersionChanged, 300),
+                ("removed", ChangeKind::Removed, -70),
+                ("grown", ChangeKind::SizeChanged, 20),
+            ]
+        );
+        assert_eq!(diff.entries[1].describe(), "1.0.0 -> 2.0.0");
+        assert_eq!(diff.delta, 1020 + 300 - 70 + 20);
+
+        let mut out = Vec::new();
+        write_diff_text(&diff, &mut out)?;
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.starts_with("Total: "));
+        assert!(text.contains("     +1020 B  added     added 0.1.0\n"));
+
+        let diff = diff_scans(&snapshot.modules, &after, SizeMetric::Disk);
+        assert_eq!(diff.new_total, after.iter().map(|m| m.disk_size).sum::<u64>());
+        let added = after.iter().find(|m| m.name == "added").unwrap();
+        let entry = diff.entries.iter().find(|e| e.name == "added").unwrap();
+        assert_eq!(entry.delta, added.disk_size as i64);
+        assert_ne!(entry.delta, 1020);
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_size_delta() {
+        assert_eq!(format_size_delta(2048), "+2.00 KB");
+        assert_eq!(format_size_delta(-300), "-300 B");
+        assert_eq!(format_size_delta(0), "+0 B");
+    }
+}
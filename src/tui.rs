@@ -0,0 +1,1612 @@
+use ratatui::{
+    backend::CrosstermBackend,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span, Text},
+    widgets::{Block, Borders, Clear, Gauge, Paragraph, Row, Table, Wrap},
+    Terminal,
+};
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    io,
+    sync::mpsc::Receiver,
+    thread::JoinHandle,
+    time::Duration,
+};
+use crossterm::{
+    event::{self, Event, KeyCode},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+
+use crate::cli::{ScanArgs, SizeMetric};
+use crate::duplicates::{find_duplicates, DuplicateSet};
+use crate::lockfile::{DependencyKind, PackageGraph, RetainedSize};
+use crate::scanner::{format_size, ModuleInfo, ScanError, ScanEvent};
+use crate::search::Query;
+use crate::snapshot::{format_size_delta, ChangeKind, ScanDiff};
+use crate::Project;
+
+/// How often the screen is redrawn while a scan is running.
+const SCAN_REFRESH: Duration = Duration::from_millis(100);
+
+/// A scan running on another thread, reporting packages as they are measured.
+pub struct BackgroundScan {
+    pub events: Receiver<ScanEvent>,
+    pub handle: JoinHandle<io::Result<Project>>,
+}
+
+/// Progress of the background scan.
+#[derive(Debug, Default)]
+struct ScanStatus {
+    /// Number of packages to measure, once they are all found
+    packages: Option<usize>,
+    measured: usize,
+    bytes: u64,
+}
+
+enum AppMode {
+    List,
+    Detail,
+    Diff,
+    Duplicates,
+    Errors,
+}
+
+/// Column the list view is sorted by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SortColumn {
+    /// Apparent size or disk usage, depending on the metric
+    Size,
+    Name,
+    Version,
+    Kind,
+    Files,
+    Deps,
+    License,
+    Modified,
+}
+
+impl SortColumn {
+    const ALL: [SortColumn; 8] = [
+        SortColumn::Size,
+        SortColumn::Name,
+        SortColumn::Version,
+        SortColumn::Kind,
+        SortColumn::Files,
+        SortColumn::Deps,
+        SortColumn::License,
+        SortColumn::Modified,
+    ];
+
+    /// Counts are sorted largest first and dates newest first, text A to Z.
+    fn descending_by_default(self) -> bool {
+        matches!(self, SortColumn::Size | SortColumn::Files | SortColumn::Deps | SortColumn::Modified)
+    }
+
+    /// The value of a module the column sorts by, `None` for modules without one.
+    fn key(self, module: &ModuleInfo, metric: SizeMetric) -> Option<SortKey<'_>> {
+        let count = |count: Option<usize>| count.map(|count| SortKey::Number(count as u64));
+        match self {
+            SortColumn::Size => Some(SortKey::Number(module.size_by(metric))),
+            SortColumn::Name => Some(SortKey::Text(&module.name)),
+            SortColumn::Version => module.version.as_deref().map(|version| SortKey::Version(version_parts(version))),
+            SortColumn::Kind => module.dependency_kind.map(|kind| SortKey::Text(kind.label())),
+            SortColumn::Files => count(module.files_count),
+            SortColumn::Deps => count(module.dependency_count),
+            SortColumn::License => module.license.as_deref().map(SortKey::Text),
+            SortColumn::Modified => module.last_modified.map(SortKey::Number),
+        }
+    }
+}
+
+/// A value to sort modules by, only compared within the same column.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum SortKey<'a> {
+    Number(u64),
+    Text(&'a str),
+    Version(Vec<Result<u64, &'a str>>),
+}
+
+/// Splits a version so numeric parts compare as numbers, `1.10.0` after `1.9.0`.
+fn version_parts(version: &str) -> Vec<Result<u64, &str>> {
+    let version = version.split('+').next().unwrap_or(version);
+    version
+        .split(['.', '-'])
+        .map(|part| part.parse().map_err(|_| part))
+        .collect()
+}
+
+/// A single line of the duplicates view: a duplicated package, or one of its
+/// copies given by set and copy index.
+enum DuplicateRow {
+    Set(usize),
+    Copy(usize, usize),
+}
+
+/// A single line of the list view, either a module or a scope group header.
+enum ListRow {
+    Module(usize),
+    Scope { name: String, size: u64, count: usize },
+}
+
+struct AppState {
+    modules: Vec<ModuleInfo>,
+    scroll_offset: usize,
+    selected_index: Option<usize>,
+    mode: AppMode,
+    group_by_scope: bool,
+    /// Only list modules of this dependency kind
+    kind_filter: Option<DependencyKind>,
+    /// Dev-only packages were left out of the scan
+    production_only: bool,
+    /// Size shown first and sorted by in the size column
+    metric: SizeMetric,
+    sort: SortColumn,
+    sort_descending: bool,
+    graph: Option<PackageGraph>,
+    /// Retained sizes of the project's direct dependencies, by graph node
+    retained: HashMap<usize, RetainedSize>,
+    /// Only list the project's direct dependencies
+    direct_only: bool,
+    /// Text typed after `/`
+    search: String,
+    /// The search prompt is open and takes the keys
+    searching: bool,
+    /// The last search that parsed, filtering the list
+    query: Option<Query>,
+    /// Why the search as typed does not parse
+    search_error: Option<String>,
+    duplicates: Vec<DuplicateSet>,
+    diff: Option<ScanDiff>,
+    /// Set while the scan is still running
+    scan: Option<ScanStatus>,
+    /// Warnings of the finished scan, printed when the TUI is closed
+    warnings: Vec<String>,
+    /// Paths the finished scan could not read
+    errors: Vec<ScanError>,
+}
+
+impl AppState {
+    fn new(modules: Vec<ModuleInfo>) -> Self {
+        AppState {
+            duplicates: find_duplicates(&modules, None),
+            modules,
+            scroll_offset: 0,
+            selected_index: None,
+            mode: AppMode::List,
+            group_by_scope: false,
+            kind_filter: None,
+            production_only: false,
+            metric: SizeMetric::Apparent,
+            sort: SortColumn::Size,
+            sort_descending: true,
+            graph: None,
+            retained: HashMap::new(),
+            direct_only: false,
+            search: String::new(),
+            searching: false,
+            query: None,
+            search_error: None,
+            diff: None,
+            scan: None,
+            warnings: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    /// Adds the packages measured so far, keeping the list sorted.
+    fn receive(&mut self, events: impl Iterator<Item = ScanEvent>) {
+        self.keep_selection(|state| {
+            let mut status = state.scan.take().unwrap_or_default();
+            for event in events {
+                match event {
+                    ScanEvent::Listed(packages) => status.packages = Some(packages),
+                    ScanEvent::Measured(module) => {
+                        status.measured += 1;
+                        status.bytes += module.size;
+                        let position = state.modules.partition_point(|m| state.compare(m, &module).is_le());
+                        state.modules.insert(position, *module);
+                    }
+                }
+            }
+            state.scan = Some(status);
+        });
+    }
+
+    /// Replaces the packages streamed in so far with the complete result,
+    /// which adds nested sizes and the dependency graph.
+    fn finish_scan(&mut self, project: Project) {
+        self.keep_selection(|state| {
+            state.modules = project.modules;
+            state.sort_modules();
+            state.duplicates = find_duplicates(&state.modules, None);
+            if let Some(graph) = project.graph {
+                state.set_graph(graph);
+            }
+        });
+        if self.selected_index.is_none() && matches!(self.mode, AppMode::Detail) {
+            self.mode = AppMode::List;
+        }
+        self.scan = None;
+        self.warnings = project.warnings;
+        self.errors = project.errors;
+    }
+
+    /// Runs `update` and selects the module that was selected before, which
+    /// may have moved to another row.
+    fn keep_selection(&mut self, update: impl FnOnce(&mut Self)) {
+        let selected = self.selected_module().map(|m| m.path.clone());
+        update(self);
+        if let Some(path) = selected {
+            self.selected_index = self
+                .rows()
+                .iter()
+                .position(|row| matches!(row, ListRow::Module(i) if self.modules[*i].path == path));
+        }
+    }
+
+    /// Switches between sorting by apparent size and by disk usage.
+    fn toggle_metric(&mut self) {
+        self.metric = match self.metric {
+            SizeMetric::Apparent => SizeMetric::Disk,
+            SizeMetric::Disk => SizeMetric::Apparent,
+        };
+        if let Some(graph) = &self.graph {
+            self.retained = graph.retained_sizes(&self.modules, self.metric);
+        }
+        self.sort_modules();
+    }
+
+    /// Sorts by the next column, in that column's usual direction.
+    fn cycle_sort(&mut self) {
+        let position = SortColumn::ALL.iter().position(|&column| column == self.sort).unwrap_or(0);
+        self.sort = SortColumn::ALL[(position + 1) % SortColumn::ALL.len()];
+        self.sort_descending = self.sort.descending_by_default();
+        self.sort_modules();
+    }
+
+    fn reverse_sort(&mut self) {
+        self.sort_descending = !self.sort_descending;
+        self.sort_modules();
+    }
+
+    fn sort_modules(&mut self) {
+        let mut modules = std::mem::take(&mut self.modules);
+        modules.sort_by(|a, b| self.compare(a, b));
+        self.modules = modules;
+    }
+
+    /// Order of two modules in the list. Modules without a value for the
+    /// sort column go last in either direction.
+    fn compare(&self, a: &ModuleInfo, b: &ModuleInfo) -> Ordering {
+        match (self.sort.key(a, self.metric), self.sort.key(b, self.metric)) {
+            (Some(a), Some(b)) if self.sort_descending => b.cmp(&a),
+            (Some(a), Some(b)) => a.cmp(&b),
+            (a, b) => a.is_none().cmp(&b.is_none()),
+        }
+    }
+
+    fn set_graph(&mut self, graph: PackageGraph) {
+        self.retained = graph.retained_sizes(&self.modules, self.metric);
+        self.duplicates = find_duplicates(&self.modules, Some(&graph));
+        self.graph = Some(graph);
+    }
+
+    fn duplicate_rows(&self) -> Vec<DuplicateRow> {
+        let mut rows = Vec::new();
+        for (i, set) in self.duplicates.iter().enumerate() {
+            rows.push(DuplicateRow::Set(i));
+            rows.extend((0..set.copies.len()).map(|c| DuplicateRow::Copy(i, c)));
+        }
+        rows
+    }
+
+    /// Retained sizes of a module that is a direct dependency of the project.
+    fn retained_size(&self, module: &ModuleInfo) -> Option<RetainedSize> {
+        if module.parent.is_some() {
+            return None;
+        }
+        let index = self.graph.as_ref()?.find_module(module)?;
+        self.retained.get(&index).copied()
+    }
+
+    /// Dependency chains leading to a module, for the detail view.
+    fn dependency_chains(&self, module: &ModuleInfo) -> Vec<String> {
+        const MAX_CHAINS: usize = 5;
+        let Some(graph) = &self.graph else {
+            return Vec::new();
+        };
+        let Some(index) = graph.find_module(module) else {
+            return Vec::new();
+        };
+        graph
+            .chains_to(index, MAX_CHAINS)
+            .iter()
+            .map(|chain| graph.describe_chain(chain))
+            .collect()
+    }
+
+    fn is_visible(&self, module: &ModuleInfo) -> bool {
+        self.kind_filter.is_none_or(|kind| module.dependency_kind == Some(kind))
+            && (!self.direct_only || self.retained_size(module).is_some())
+            && self.query.as_ref().is_none_or(|query| query.matches(module))
+    }
+
+    /// Filters the list by the search as typed so far. While it does not
+    /// parse, e.g. halfway through `size>5MB`, the last search that did is kept.
+    fn set_search(&mut self, search: String) {
+        match Query::parse(&search) {
+            Ok(query) => {
+                self.query = Some(query).filter(|_| !search.trim().is_empty());
+                self.search_error = None;
+            }
+            Err(err) => self.search_error = Some(err),
+        }
+        self.search = search;
+        self.searching = true;
+        self.scroll_offset = 0;
+        self.selected_index = None;
+    }
+
+    fn clear_search(&mut self) {
+        self.search.clear();
+        self.searching = false;
+        self.query = None;
+        self.search_error = None;
+        self.scroll_offset = 0;
+        self.selected_index = None;
+    }
+
+    /// Selects the next or previous module in the list, wrapping around and
+    /// skipping scope headers, and scrolls it into the `visible` rows.
+    fn jump_to_match(&mut self, forward: bool, visible: usize) {
+        let matches: Vec<usize> = self
+            .rows()
+            .iter()
+            .enumerate()
+            .filter(|(_, row)| matches!(row, ListRow::Module(_)))
+            .map(|(i, _)| i)
+            .collect();
+        let (Some(&first), Some(&last)) = (matches.first(), matches.last()) else {
+            return;
+        };
+        let selected = match (self.selected_index, forward) {
+            (None, true) => first,
+            (None, false) => last,
+            (Some(current), true) => matches.iter().copied().find(|&i| i > current).unwrap_or(first),
+            (Some(current), false) => matches.iter().rev().copied().find(|&i| i < current).unwrap_or(last),
+        };
+        self.selected_index = Some(selected);
+        if selected < self.scroll_offset {
+            self.scroll_offset = selected;
+        } else if selected >= self.scroll_offset + visible.max(1) {
+            self.scroll_offset = selected + 1 - visible.max(1);
+        }
+    }
+
+    /// Steps the dependency kind filter through prod, dev, optional, peer and
+    /// back to showing everything.
+    fn cycle_kind_filter(&mut self) {
+        let position = self
+            .kind_filter
+            .and_then(|kind| DependencyKind::ALL.iter().position(|&k| k == kind));
+        self.kind_filter = match position {
+            None => Some(DependencyKind::ALL[0]),
+            Some(i) => DependencyKind::ALL.get(i + 1).copied(),
+        };
+    }
+
+    /// Builds the rows of the list view. Modules are expected to be sorted already.
+    fn rows(&self) -> Vec<ListRow> {
+        let visible = self.modules.iter().enumerate().filter(|(_, m)| self.is_visible(m));
+        if !self.group_by_scope {
+            return visible.map(|(i, _)| ListRow::Module(i)).collect();
+        }
+
+        // Each group is either a scope with its packages or a single unscoped
+        // module, sized by the same metric as the rows
+        let mut groups: Vec<(Option<&str>, u64, Vec<usize>)> = Vec::new();
+        let mut scope_groups: HashMap<&str, usize> = HashMap::new();
+        for (i, module) in visible {
+            match module.scope() {
+                Some(scope) => {
+                    let group = *scope_groups.entry(scope).or_insert_with(|| {
+                        groups.push((Some(scope), 0, Vec::new()));
+                        groups.len() - 1
+                    });
+                    groups[group].1 += module.size_by(self.metric);
+                    groups[group].2.push(i);
+                }
+                None => groups.push((None, module.size_by(self.metric), vec![i])),
+            }
+        }
+        groups.sort_by_key(|g| std::cmp::Reverse(g.1));
+
+        let mut rows = Vec::new();
+        for (scope, size, members) in groups {
+            if let Some(scope) = scope {
+                rows.push(ListRow::Scope {
+                    name: scope.to_string(),
+                    size,
+                    count: members.len(),
+                });
+            }
+            rows.extend(members.into_iter().map(ListRow::Module));
+        }
+        rows
+    }
+
+    fn selected_module(&self) -> Option<&ModuleInfo> {
+        let selected = self.selected_index?;
+        match self.rows().into_iter().nth(selected)? {
+            ListRow::Module(idx) => self.modules.get(idx),
+            ListRow::Scope { .. } => None,
+        }
+    }
+}
+
+fn render_detail_view(
+    module: &ModuleInfo,
+    retained: Option<RetainedSize>,
+    chains: &[String],
+    area: Rect,
+    f: &mut ratatui::Frame,
+) {
+    let block = Block::default()
+        .title(format!("Module Details: {}", module.name))
+        .borders(Borders::ALL);
+    
+    let inner_area = block.inner(area);
+    f.render_widget(block, area);
+    
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(18), // Basic info
+            Constraint::Length(1),  // Separator 
+            Constraint::Length(if chains.is_empty() { 0 } else { chains.len() as u16 + 2 }), // Why installed
+            Constraint::Min(5),     // File types
+        ].as_ref())
+        .split(inner_area);
+    
+    // Basic info section
+    let mut info_text = Vec::new();
+    info_text.push(Line::from(vec![
+        Span::styled("Size: ", Style::default().fg(Color::Yellow)),
+        Span::raw(format!("{} ({} on disk)", format_size(module.size), format_size(module.disk_size))),
+    ]));
+    
+    if module.partial {
+        info_text.push(Line::from(vec![
+            Span::styled("Partial: ", Style::default().fg(Color::Red)),
+            Span::raw("some files could not be read, see the errors view ('e')"),
+        ]));
+    }
+    
+    if module.shared_size > 0 {
+        info_text.push(Line::from(vec![
+            Span::styled("Hard-linked: ", Style::default().fg(Color::Yellow)),
+            Span::raw(format!("{} more, already counted elsewhere", format_size(module.shared_size))),
+        ]));
+    }
+    
+    if module.nested_size > 0 {
+        info_text.push(Line::from(vec![
+            Span::styled("Size with Nested: ", Style::default().fg(Color::Yellow)),
+            Span::raw(format_size(module.total_size())),
+        ]));
+    }
+    
+    if let Some(retained) = retained {
+        info_text.push(Line::from(vec![
+            Span::styled("Size with Dependencies: ", Style::default().fg(Color::Yellow)),
+            Span::raw(format_size(retained.closure)),
+        ]));
+        info_text.push(Line::from(vec![
+            Span::styled("Freed if Removed: ", Style::default().fg(Color::Yellow)),
+            Span::raw(format_size(retained.exclusive)),
+        ]));
+    }
+    
+    info_text.push(Line::from(vec![
+        Span::styled("Path: ", Style::default().fg(Color::Yellow)),
+        Span::raw(&module.path),
+    ]));
+    
+    if let Some(target) = &module.link_target {
+        info_text.push(Line::from(vec![
+            Span::styled("Symlink To: ", Style::default().fg(Color::Yellow)),
+            Span::raw(target),
+        ]));
+    }
+    
+    if let Some(parent) = module.parent_name() {
+        info_text.push(Line::from(vec![
+            Span::styled("Nested In: ", Style::default().fg(Color::Yellow)),
+            Span::raw(format!("{} (depth {})", parent, module.depth)),
+        ]));
+    }
+    
+    if let Some(version) = &module.version {
+        info_text.push(Line::from(vec![
+            Span::styled("Version: ", Style::default().fg(Color::Yellow)),
+            Span::raw(version),
+        ]));
+    }
+    
+    if let Some(license) = &module.license {
+        info_text.push(Line::from(vec![
+            Span::styled("License: ", Style::default().fg(Color::Yellow)),
+            Span::raw(license),
+        ]));
+    }
+    
+    if let Some(kind) = module.dependency_kind {
+        info_text.push(Line::from(vec![
+            Span::styled("Type: ", Style::default().fg(Color::Yellow)),
+            Span::raw(kind.label()),
+        ]));
+    }
+    
+    if let Some(deps) = module.dependency_count {
+        info_text.push(Line::from(vec![
+            Span::styled("Dependencies: ", Style::default().fg(Color::Yellow)),
+            Span::raw(deps.to_string()),
+        ]));
+    }
+    
+    if let Some(files) = module.files_count {
+        info_text.push(Line::from(vec![
+            Span::styled("Files: ", Style::default().fg(Color::Yellow)),
+            Span::raw(files.to_string()),
+        ]));
+    }
+    
+    if let Some(last_updated) = &module.last_updated {
+        info_text.push(Line::from(vec![
+            Span::styled("Last Updated: ", Style::default().fg(Color::Yellow)),
+            Span::raw(last_updated),
+        ]));
+    }
+    
+    if let Some(description) = &module.description {
+        info_text.push(Line::from(vec![
+            Span::styled("Description: ", Style::default().fg(Color::Yellow)),
+            Span::raw(description),
+        ]));
+    }
+    
+    let basic_info = Paragraph::new(info_text)
+        .block(Block::default().borders(Borders::NONE))
+        .wrap(Wrap { trim: true });
+    
+    f.render_widget(basic_info, chunks[0]);
+    
+    // Dependency chains leading to the module
+    if !chains.is_empty() {
+        let mut why_text = vec![Line::from(
+            Span::styled("Required By:", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD))
+        )];
+        why_text.extend(chains.iter().map(|chain| Line::from(Span::raw(chain.as_str()))));
+        let why_info = Paragraph::new(why_text)
+            .block(Block::default().borders(Borders::NONE));
+        f.render_widget(why_info, chunks[2]);
+    }
+    
+    // File types section
+    let mut file_type_text = Vec::new();
+    file_type_text.push(Line::from(
+        Span::styled("File Types:", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD))
+    ));
+    
+    if let Some(file_types) = &module.file_types {
+        for (ext, count) in file_types.iter().take(10) {  // Limit to top 10 types
+            file_type_text.push(Line::from(vec![
+                Span::styled(format!("{}: ", ext), Style::default().fg(Color::Blue)),
+                Span::raw(count.to_string()),
+                Span::raw(" files"),
+            ]));
+        }
+        
+        if file_types.len() > 10 {
+            file_type_text.push(Line::from(
+                Span::styled("(and more...)", Style::default().fg(Color::DarkGray))
+            ));
+        }
+    } else {
+        file_type_text.push(Line::from(
+            Span::styled("No file type information available", Style::default().fg(Color::DarkGray))
+        ));
+    }
+    
+    let file_types_info = Paragraph::new(file_type_text)
+        .block(Block::default().borders(Borders::NONE))
+        .wrap(Wrap { trim: true });
+    
+    f.render_widget(file_types_info, chunks[3]);
+    
+    // Links and navigation help at the bottom
+    let help_text = Text::from(vec![
+        Line::from(vec![
+            Span::styled("ESC", Style::default().fg(Color::Yellow)),
+            Span::raw(" to return to list view | "),
+            Span::styled("q", Style::default().fg(Color::Yellow)),
+            Span::raw(" to quit"),
+        ]),
+    ]);
+    
+    let help_paragraph = Paragraph::new(help_text)
+        .style(Style::default().fg(Color::White))
+        .alignment(ratatui::layout::Alignment::Center);
+    
+    let help_area = Rect::new(
+        area.x + 1,
+        area.y + area.height - 2,
+        area.width - 2,
+        1,
+    );
+    
+    f.render_widget(help_paragraph, help_area);
+}
+
+fn render_list_view(app_state: &mut AppState, rows: &[ListRow], size: Rect, f: &mut ratatui::Frame) {
+    let total_items = rows.len();
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Min(0),
+            Constraint::Length(if app_state.searching || app_state.query.is_some() { 3 } else { 0 }), // Search
+            Constraint::Length(if app_state.scan.is_some() { 3 } else { 0 }), // Scan progress
+        ].as_ref())
+        .split(size);
+
+    // Calculate visible area based on terminal size
+    // Subtract 4 for header row and borders
+    let max_visible_items = (chunks[0].height as usize).saturating_sub(4);
+    
+    // Ensure scroll offset doesn't go beyond available items
+    if app_state.scroll_offset > total_items.saturating_sub(max_visible_items) {
+        app_state.scroll_offset = total_items.saturating_sub(max_visible_items);
+    }
+    
+    // Create rows from visible range of modules
+    let selected_style = Style::default().bg(Color::DarkGray);
+    
+    let table_rows: Vec<Row> = rows
+        .iter()
+        .enumerate()
+        .skip(app_state.scroll_offset)
+        .take(max_visible_items)
+        .map(|(i, row)| {
+            let style = match app_state.selected_index {
+                Some(selected) if selected == i => selected_style,
+                _ => Style::default(),
+            };
+            
+            match row {
+                ListRow::Module(idx) => {
+                    let m = &app_state.modules[*idx];
+                    let mut name = if app_state.group_by_scope && m.scope().is_some() {
+                        format!("  {}", m.name)
+                    } else {
+                        m.name.clone()
+                    };
+                    if let Some(parent) = m.parent_name() {
+                        name.push_str(&format!(" (in {})", parent));
+                    }
+                    if let Some(target) = &m.link_target {
+                        name.push_str(&format!(" -> {}", target));
+                    }
+                    if m.partial {
+                        name.push_str(" (partial)");
+                    }
+                    let kind = m.dependency_kind.map(|k| k.label()).unwrap_or("-");
+                    let (closure, exclusive) = match app_state.retained_size(m) {
+                        Some(retained) => (format_size(retained.closure), format_size(retained.exclusive)),
+                        None => (String::new(), String::new()),
+                    };
+                    let count = |count: Option<usize>| count.map(|count| count.to_string()).unwrap_or_default();
+                    Row::new(vec![
+                        name,
+                        m.version.clone().unwrap_or_default(),
+                        kind.to_string(),
+                        format_size(m.size),
+                        format_size(m.disk_size),
+                        count(m.files_count),
+                        count(m.dependency_count),
+                        m.license.clone().unwrap_or_default(),
+                        m.last_updated.clone().unwrap_or_default(),
+                        closure,
+                        exclusive,
+                    ]).style(style)
+                },
+                ListRow::Scope { name, size, count } => {
+                    let mut cells = vec![String::new(); 11];
+                    cells[0] = format!("{}/* ({} packages)", name, count);
+                    let size_column = match app_state.metric {
+                        SizeMetric::Apparent => 3,
+                        SizeMetric::Disk => 4,
+                    };
+                    cells[size_column] = format_size(*size);
+                    Row::new(cells).style(style.add_modifier(Modifier::BOLD))
+                },
+            }
+        })
+        .collect();
+
+    // Create scroll indicator for title
+    let scroll_indicator = if total_items > max_visible_items {
+        format!(" [{}-{}/{}]", 
+            app_state.scroll_offset + 1, 
+            (app_state.scroll_offset + table_rows.len()).min(total_items),
+            total_items)
+    } else {
+        String::new()
+    };
+
+    let grouping = if app_state.group_by_scope { " (grouped by scope)" } else { "" };
+    let direct = if app_state.direct_only { " (direct dependencies)" } else { "" };
+    let filter = match app_state.kind_filter {
+        Some(kind) => {
+            let filtered = app_state.modules.iter().filter(|m| app_state.is_visible(m));
+            let (count, size) = filtered.fold((0, 0), |(count, size), m| (count + 1, size + m.size_by(app_state.metric)));
+            let on_disk = if app_state.metric == SizeMetric::Disk { " on disk" } else { "" };
+            format!(" [{} only: {} packages, {}{}]", kind.label(), count, format_size(size), on_disk)
+        },
+        None => String::new(),
+    };
+    let production = if app_state.production_only { " (production only)" } else { "" };
+    let metric = match (app_state.sort, app_state.metric) {
+        (SortColumn::Size, SizeMetric::Disk) => " (by disk usage)",
+        _ => "",
+    };
+    let errors = match app_state.errors.len() {
+        0 => String::new(),
+        1 => " [1 error]".to_string(),
+        count => format!(" [{} errors]", count),
+    };
+    let title = format!(
+        "Node Modules Size{}{}{}{}{}{}{}",
+        production, metric, direct, grouping, filter, errors, scroll_indicator
+    );
+
+    // The arrow marks the sort column, the size column follows the metric
+    let sorted_header = match app_state.sort {
+        SortColumn::Size if app_state.metric == SizeMetric::Disk => "Disk",
+        SortColumn::Size => "Size",
+        SortColumn::Name => "Module",
+        SortColumn::Version => "Version",
+        SortColumn::Kind => "Type",
+        SortColumn::Files => "Files",
+        SortColumn::Deps => "Deps",
+        SortColumn::License => "License",
+        SortColumn::Modified => "Modified",
+    };
+    let arrow = if app_state.sort_descending { " ▼" } else { " ▲" };
+    let header: Vec<String> = [
+        "Module", "Version", "Type", "Size", "Disk", "Files", "Deps", "License", "Modified", "With Deps", "Exclusive",
+    ]
+    .iter()
+    .map(|&column| if column == sorted_header { format!("{}{}", column, arrow) } else { column.to_string() })
+    .collect();
+
+    let table = Table::new(table_rows)
+        .header(Row::new(header).style(Style::default().fg(Color::Yellow)))
+        .block(Block::default()
+            .title(title)
+            .borders(Borders::ALL))
+        .widths(&[
+            Constraint::Percentage(22),
+            Constraint::Percentage(8),
+            Constraint::Percentage(5),
+            Constraint::Percentage(8),
+            Constraint::Percentage(8),
+            Constraint::Percentage(6),
+            Constraint::Percentage(5),
+            Constraint::Percentage(9),
+            Constraint::Percentage(11),
+            Constraint::Percentage(9),
+            Constraint::Percentage(9),
+        ]);
+
+    f.render_widget(table, chunks[0]);
+    
+    // Add help text at the bottom
+    let help_text = Text::from(vec![
+        Line::from(vec![
+            Span::styled(" ↑/↓: Navigate | ", Style::default().fg(Color::Gray)),
+            Span::styled("Enter: ", Style::default().fg(Color::Yellow)),
+            Span::styled("View Details | ", Style::default().fg(Color::Gray)),
+            Span::styled("g: ", Style::default().fg(Color::Yellow)),
+            Span::styled("Group by Scope | ", Style::default().fg(Color::Gray)),
+            Span::styled("f: ", Style::default().fg(Color::Yellow)),
+            Span::styled("Filter Type | ", Style::default().fg(Color::Gray)),
+            Span::styled("d: ", Style::default().fg(Color::Yellow)),
+            Span::styled("Direct Deps | ", Style::default().fg(Color::Gray)),
+            Span::styled("u: ", Style::default().fg(Color::Yellow)),
+            Span::styled("Duplicates | ", Style::default().fg(Color::Gray)),
+            Span::styled("m: ", Style::default().fg(Color::Yellow)),
+            Span::styled("Size/Disk | ", Style::default().fg(Color::Gray)),
+            Span::styled("/: ", Style::default().fg(Color::Yellow)),
+            Span::styled("Search | ", Style::default().fg(Color::Gray)),
+            Span::styled("s/r: ", Style::default().fg(Color::Yellow)),
+            Span::styled("Sort/Reverse | ", Style::default().fg(Color::Gray)),
+            Span::styled("e: ", Style::default().fg(Color::Yellow)),
+            Span::styled("Errors | ", Style::default().fg(Color::Gray)),
+            Span::styled("q: ", Style::default().fg(Color::Yellow)),
+            Span::styled("Quit", Style::default().fg(Color::Gray)),
+        ]),
+    ]);
+    
+    let help_paragraph = Paragraph::new(help_text)
+        .style(Style::default().fg(Color::White))
+        .alignment(ratatui::layout::Alignment::Center);
+    
+    let help_area = Rect::new(
+        chunks[0].x,
+        chunks[0].y + chunks[0].height - 1,
+        chunks[0].width,
+        1,
+    );
+    
+    f.render_widget(help_paragraph, help_area);
+
+    if app_state.searching || app_state.query.is_some() {
+        let matches = rows.iter().filter(|row| matches!(row, ListRow::Module(_))).count();
+        let (title, color) = match &app_state.search_error {
+            Some(err) => (format!("Search: {}", err), Color::Red),
+            None => (format!("Search: {} matches (n/N: next/previous, Esc: clear)", matches), Color::Yellow),
+        };
+        let cursor = if app_state.searching { "_" } else { "" };
+        let search = Paragraph::new(format!("/{}{}", app_state.search, cursor))
+            .block(Block::default().title(title).borders(Borders::ALL).border_style(Style::default().fg(color)));
+        f.render_widget(search, chunks[1]);
+    }
+
+    if let Some(status) = &app_state.scan {
+        let (ratio, packages) = match status.packages {
+            Some(0) => (1.0, "0".to_string()),
+            Some(packages) => (status.measured as f64 / packages as f64, packages.to_string()),
+            None => (0.0, "?".to_string()),
+        };
+        let gauge = Gauge::default()
+            .block(Block::default().title("Scanning").borders(Borders::ALL))
+            .gauge_style(Style::default().fg(Color::Green))
+            .ratio(ratio.min(1.0))
+            .label(format!(
+                "{}/{} packages, {} seen",
+                status.measured,
+                packages,
+                format_size(status.bytes)
+            ));
+        f.render_widget(gauge, chunks[2]);
+    }
+}
+
+fn render_diff_view(app_state: &mut AppState, size: Rect, f: &mut ratatui::Frame) {
+    let Some(diff) = &app_state.diff else {
+        return;
+    };
+
+    // Subtract 4 for header row and borders
+    let max_visible_items = (size.height as usize).saturating_sub(4);
+    let total_items = diff.entries.len();
+    if app_state.scroll_offset > total_items.saturating_sub(max_visible_items) {
+        app_state.scroll_offset = total_items.saturating_sub(max_visible_items);
+    }
+
+    let table_rows: Vec<Row> = diff.entries
+        .iter()
+        .enumerate()
+        .skip(app_state.scroll_offset)
+        .take(max_visible_items)
+        .map(|(i, entry)| {
+            let color = match entry.kind {
+                ChangeKind::Added => Color::Green,
+                ChangeKind::Removed => Color::Red,
+                ChangeKind::VersionChanged => Color::Cyan,
+                ChangeKind::SizeChanged => Color::White,
+            };
+            let mut style = Style::default().fg(color);
+            if app_state.selected_index == Some(i) {
+                style = style.bg(Color::DarkGray);
+            }
+            Row::new(vec![
+                format_size_delta(entry.delta),
+                entry.name.clone(),
+                entry.describe(),
+            ]).style(style)
+        })
+        .collect();
+
+    let title = format!(
+        "Changes: {} -> {} ({}), {} packages",
+        format_size(diff.old_total),
+        format_size(diff.new_total),
+        format_size_delta(diff.delta),
+        total_items
+    );
+    let table = Table::new(table_rows)
+        .header(Row::new(vec!["Delta", "Package", "Change"]).style(Style::default().fg(Color::Yellow)))
+        .block(Block::default().title(title).borders(Borders::ALL))
+        .widths(&[
+            Constraint::Length(12),
+            Constraint::Percentage(40),
+            Constraint::Percentage(50),
+        ]);
+    f.render_widget(table, size);
+
+    let help_text = Text::from(vec![
+        Line::from(vec![
+            Span::styled(" ↑/↓: Navigate | ", Style::default().fg(Color::Gray)),
+            Span::styled("c: ", Style::default().fg(Color::Yellow)),
+            Span::styled("Module List | ", Style::default().fg(Color::Gray)),
+            Span::styled("q: ", Style::default().fg(Color::Yellow)),
+            Span::styled("Quit", Style::default().fg(Color::Gray)),
+        ]),
+    ]);
+    let help_paragraph = Paragraph::new(help_text)
+        .style(Style::default().fg(Color::White))
+        .alignment(ratatui::layout::Alignment::Center);
+    let help_area = Rect::new(size.x, size.y + size.height - 1, size.width, 1);
+    f.render_widget(help_paragraph, help_area);
+}
+
+fn render_duplicates_view(app_state: &mut AppState, size: Rect, f: &mut ratatui::Frame) {
+    let rows = app_state.duplicate_rows();
+
+    // Subtract 4 for header row and borders
+    let max_visible_items = (size.height as usize).saturating_sub(4);
+    let total_items = rows.len();
+    if app_state.scroll_offset > total_items.saturating_sub(max_visible_items) {
+        app_state.scroll_offset = total_items.saturating_sub(max_visible_items);
+    }
+
+    let table_rows: Vec<Row> = rows
+        .iter()
+        .enumerate()
+        .skip(app_state.scroll_offset)
+        .take(max_visible_items)
+        .map(|(i, row)| {
+            let mut style = Style::default();
+            if app_state.selected_index == Some(i) {
+                style = style.bg(Color::DarkGray);
+            }
+            match *row {
+                DuplicateRow::Set(s) => {
+                    let set = &app_state.duplicates[s];
+                    Row::new(vec![
+                        format!("{} ({} copies)", set.name, set.copies.len()),
+                        format_size(set.total_size),
+                        format_size(set.wasted),
+                        set.versions(),
+                    ]).style(style.add_modifier(Modifier::BOLD))
+                },
+                DuplicateRow::Copy(s, c) => {
+                    let copy = &app_state.duplicates[s].copies[c];
+                    Row::new(vec![
+                        format!("  {} {}", copy.version.as_deref().unwrap_or("?"), copy.path),
+                        format_size(copy.size),
+                        String::new(),
+                        copy.dependents.join(", "),
+                    ]).style(style)
+                },
+            }
+        })
+        .collect();
+
+    let wasted: u64 = app_state.duplicates.iter().map(|d| d.wasted).sum();
+    let title = format!(
+        "Duplicates: {} packages installed more than once, {} wasted",
+        app_state.duplicates.len(),
+        format_size(wasted)
+    );
+    let table = Table::new(table_rows)
+        .header(Row::new(vec!["Package", "Size", "Wasted", "Versions / Required By"]).style(Style::default().fg(Color::Yellow)))
+        .block(Block::default().title(title).borders(Borders::ALL))
+        .widths(&[
+            Constraint::Percentage(40),
+            Constraint::Percentage(12),
+            Constraint::Percentage(12),
+            Constraint::Percentage(36),
+        ]);
+    f.render_widget(table, size);
+
+    let help_text = Text::from(vec![
+        Line::from(vec![
+            Span::styled(" ↑/↓: Navigate | ", Style::default().fg(Color::Gray)),
+            Span::styled("u: ", Style::default().fg(Color::Yellow)),
+            Span::styled("Module List | ", Style::default().fg(Color::Gray)),
+            Span::styled("q: ", Style::default().fg(Color::Yellow)),
+            Span::styled("Quit", Style::default().fg(Color::Gray)),
+        ]),
+    ]);
+    let help_paragraph = Paragraph::new(help_text)
+        .style(Style::default().fg(Color::White))
+        .alignment(ratatui::layout::Alignment::Center);
+    let help_area = Rect::new(size.x, size.y + size.height - 1, size.width, 1);
+    f.render_widget(help_paragraph, help_area);
+}
+
+fn render_errors_view(app_state: &mut AppState, size: Rect, f: &mut ratatui::Frame) {
+    // Subtract 4 for header row and borders
+    let max_visible_items = (size.height as usize).saturating_sub(4);
+    let total_items = app_state.errors.len();
+    if app_state.scroll_offset > total_items.saturating_sub(max_visible_items) {
+        app_state.scroll_offset = total_items.saturating_sub(max_visible_items);
+    }
+
+    let table_rows: Vec<Row> = app_state.errors
+        .iter()
+        .enumerate()
+        .skip(app_state.scroll_offset)
+        .take(max_visible_items)
+        .map(|(i, error)| {
+            let mut style = Style::default();
+            if app_state.selected_index == Some(i) {
+                style = style.bg(Color::DarkGray);
+            }
+            Row::new(vec![error.path.clone(), error.message.clone()]).style(style)
+        })
+        .collect();
+
+    let partial = app_state.modules.iter().filter(|m| m.partial).count();
+    let title = format!(
+        "Errors: {} paths could not be read or parsed, {} packages measured partially",
+        total_items, partial
+    );
+    let table = Table::new(table_rows)
+        .header(Row::new(vec!["Path", "Error"]).style(Style::default().fg(Color::Yellow)))
+        .block(Block::default().title(title).borders(Borders::ALL))
+        .widths(&[
+            Constraint::Percentage(60),
+            Constraint::Percentage(40),
+        ]);
+    f.render_widget(table, size);
+
+    let help_text = Text::from(vec![
+        Line::from(vec![
+            Span::styled(" ↑/↓: Navigate | ", Style::default().fg(Color::Gray)),
+            Span::styled("e: ", Style::default().fg(Color::Yellow)),
+            Span::styled("Module List | ", Style::default().fg(Color::Gray)),
+            Span::styled("q: ", Style::default().fg(Color::Yellow)),
+            Span::styled("Quit", Style::default().fg(Color::Gray)),
+        ]),
+    ]);
+    let help_paragraph = Paragraph::new(help_text)
+        .style(Style::default().fg(Color::White))
+        .alignment(ratatui::layout::Alignment::Center);
+    let help_area = Rect::new(size.x, size.y + size.height - 1, size.width, 1);
+    f.render_widget(help_paragraph, help_area);
+}
+
+/// Opens the TUI right away and fills the list in as the scan proceeds.
+pub fn run_app(scan: BackgroundScan, args: &ScanArgs) -> io::Result<()> {
+    let mut app_state = AppState::new(Vec::new());
+    app_state.production_only = args.prod;
+    app_state.metric = args.metric;
+    app_state.scan = Some(ScanStatus::default());
+    run(app_state, Some(scan))
+}
+
+/// Opens the TUI on the comparison with a snapshot, `c` switches to the module list.
+pub fn run_diff(modules: Vec<ModuleInfo>, diff: ScanDiff) -> io::Result<()> {
+    let mut app_state = AppState::new(modules);
+    app_state.diff = Some(diff);
+    app_state.mode = AppMode::Diff;
+    run(app_state, None)
+}
+
+fn run(mut app_state: AppState, mut scan: Option<BackgroundScan>) -> io::Result<()> {
+    enable_raw_mode()?;
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen)?;
+    let backend = CrosstermBackend::new(stdout);
+    let mut terminal = Terminal::new(backend)?;
+    let mut scan_error = None;
+
+    loop {
+        if let Some(running) = &scan {
+            app_state.receive(running.events.try_iter());
+            if running.handle.is_finished() {
+                let result = scan.take().unwrap().handle.join().unwrap_or_else(|_| {
+                    Err(io::Error::other("the scan stopped unexpectedly"))
+                });
+                match result {
+                    Ok(project) => app_state.finish_scan(project),
+                    Err(err) => {
+                        scan_error = Some(err);
+                        break;
+                    }
+                }
+            }
+        }
+
+        let rows = app_state.rows();
+        let total_items = match (&app_state.mode, &app_state.diff) {
+            (AppMode::Diff, Some(diff)) => diff.entries.len(),
+            (AppMode::Duplicates, _) => app_state.duplicate_rows().len(),
+            (AppMode::Errors, _) => app_state.errors.len(),
+            _ => rows.len(),
+        };
+
+        terminal.draw(|f| {
+            let size = f.size();
+            
+            match app_state.mode {
+                AppMode::List => render_list_view(&mut app_state, &rows, size, f),
+                AppMode::Diff => render_diff_view(&mut app_state, size, f),
+                AppMode::Duplicates => render_duplicates_view(&mut app_state, size, f),
+                AppMode::Errors => render_errors_view(&mut app_state, size, f),
+                AppMode::Detail => {
+                    if let Some(module) = app_state.selected_module() {
+                        // Add 10% padding on all sides
+                        let detail_area = Rect::new(
+                            size.x + size.width / 10,
+                            size.y + size.height / 10,
+                            size.width * 8 / 10,
+                            size.height * 8 / 10,
+                        );
+                        
+                        // First render background
+                        f.render_widget(Clear, detail_area);
+                        
+                        // Then render detail view
+                        let chains = app_state.dependency_chains(module);
+                        render_detail_view(module, app_state.retained_size(module), &chains, detail_area, f);
+                    }
+                },
+            }
+        })?;
+
+        // Keep redrawing while packages come in, quitting does not wait for the scan
+        if scan.is_some() && !event::poll(SCAN_REFRESH)? {
+            continue;
+        }
+        if let Event::Key(key) = event::read()? {
+            if app_state.searching && matches!(app_state.mode, AppMode::List) {
+                match key.code {
+                    KeyCode::Char(c) => {
+                        let search = format!("{}{}", app_state.search, c);
+                        app_state.set_search(search);
+                    },
+                    KeyCode::Backspace => {
+                        let mut search = app_state.search.clone();
+                        search.pop();
+                        app_state.set_search(search);
+                    },
+                    KeyCode::Enter => app_state.searching = false,
+                    KeyCode::Esc => app_state.clear_search(),
+                    _ => {}
+                }
+                continue;
+            }
+            match app_state.mode {
+                AppMode::List | AppMode::Diff | AppMode::Duplicates | AppMode::Errors => match key.code {
+                    KeyCode::Char('q') => break,
+                    KeyCode::Up | KeyCode::Char('k') => {
+                        if app_state.selected_index.is_none() {
+                            app_state.selected_index = Some(app_state.scroll_offset);
+                        } else if let Some(selected) = app_state.selected_index {
+                            if selected > 0 {
+                                app_state.selected_index = Some(selected - 1);
+                                
+                                // Adjust scroll if necessary
+                                if selected < app_state.scroll_offset + 1 {
+                                    app_state.scroll_offset = app_state.scroll_offset.saturating_sub(1);
+                                }
+                            }
+                        }
+                    },
+                    KeyCode::Down | KeyCode::Char('j') => {
+                        if app_state.selected_index.is_none() {
+                            app_state.selected_index = Some(app_state.scroll_offset);
+                        } else if let Some(selected) = app_state.selected_index {
+                            if selected + 1 < total_items {
+                                app_state.selected_index = Some(selected + 1);
+                                
+                                // Get visible height
+                                let visible_height = terminal.size()?.height as usize - 4;
+                                
+                                // Adjust scroll if necessary
+                                if selected >= app_state.scroll_offset + visible_height - 1 {
+                                    app_state.scroll_offset += 1;
+                                }
+                            }
+                        }
+                    },
+                    KeyCode::PageUp => {
+                        // Terminal size - 4 (header + borders)
+                        let page_size = terminal.size()?.height as usize - 4;
+                        app_state.scroll_offset = app_state.scroll_offset.saturating_sub(page_size);
+                        
+                        // Also adjust selected item
+                        if let Some(selected) = app_state.selected_index {
+                            let new_selected = selected.saturating_sub(page_size);
+                            app_state.selected_index = Some(new_selected);
+                        }
+                    },
+                    KeyCode::PageDown => {
+                        // Terminal size - 4 (header + borders)
+                        let page_size = terminal.size()?.height as usize - 4;
+                        let max_scroll = total_items.saturating_sub(page_size);
+                        
+                        app_state.scroll_offset = (app_state.scroll_offset + page_size).min(max_scroll);
+                        
+                        // Also adjust selected item
+                        if let Some(selected) = app_state.selected_index {
+                            let new_selected = (selected + page_size).min(total_items.saturating_sub(1));
+                            app_state.selected_index = Some(new_selected);
+                        }
+                    },
+                    KeyCode::Home => {
+                        app_state.scroll_offset = 0;
+                        if app_state.selected_index.is_some() {
+                            app_state.selected_index = Some(0);
+                        }
+                    },
+                    KeyCode::End => {
+                        // Go to last page
+                        let max_visible_items = terminal.size()?.height as usize - 4;
+                        app_state.scroll_offset = total_items.saturating_sub(max_visible_items);
+                        
+                        if app_state.selected_index.is_some() {
+                            app_state.selected_index = Some(total_items.saturating_sub(1));
+                        }
+                    },
+                    KeyCode::Enter if matches!(app_state.mode, AppMode::List) && app_state.selected_module().is_some() => {
+                        app_state.mode = AppMode::Detail;
+                    },
+                    KeyCode::Char('c') if app_state.diff.is_some() => {
+                        app_state.mode = match app_state.mode {
+                            AppMode::Diff => AppMode::List,
+                            _ => AppMode::Diff,
+                        };
+                        app_state.scroll_offset = 0;
+                        app_state.selected_index = None;
+                    },
+                    KeyCode::Char('u') => {
+                        app_state.mode = match app_state.mode {
+                            AppMode::Duplicates => AppMode::List,
+                            _ => AppMode::Duplicates,
+                        };
+                        app_state.scroll_offset = 0;
+                        app_state.selected_index = None;
+                    },
+                    KeyCode::Char('e') => {
+                        app_state.mode = match app_state.mode {
+                            AppMode::Errors => AppMode::List,
+                            _ => AppMode::Errors,
+                        };
+                        app_state.scroll_offset = 0;
+                        app_state.selected_index = None;
+                    },
+                    KeyCode::Char('g') if matches!(app_state.mode, AppMode::List) => {
+                        app_state.group_by_scope = !app_state.group_by_scope;
+                        app_state.scroll_offset = 0;
+                        app_state.selected_index = None;
+                    },
+                    KeyCode::Char('m') if matches!(app_state.mode, AppMode::List) => {
+                        app_state.toggle_metric();
+                        app_state.scroll_offset = 0;
+                        app_state.selected_index = None;
+                    },
+                    KeyCode::Char('/') if matches!(app_state.mode, AppMode::List) => {
+                        app_state.searching = true;
+                    },
+                    KeyCode::Char('n') | KeyCode::Char('N') if matches!(app_state.mode, AppMode::List) => {
+                        let visible = (terminal.size()?.height as usize).saturating_sub(7);
+                        app_state.jump_to_match(key.code == KeyCode::Char('n'), visible);
+                    },
+                    KeyCode::Esc if matches!(app_state.mode, AppMode::List) => {
+                        app_state.clear_search();
+                    },
+                    KeyCode::Char('s') if matches!(app_state.mode, AppMode::List) => {
+                        app_state.cycle_sort();
+                        app_state.scroll_offset = 0;
+                        app_state.selected_index = None;
+                    },
+                    KeyCode::Char('r') if matches!(app_state.mode, AppMode::List) => {
+                        app_state.reverse_sort();
+                        app_state.scroll_offset = 0;
+                        app_state.selected_index = None;
+                    },
+                    KeyCode::Char('d') if matches!(app_state.mode, AppMode::List) => {
+                        app_state.direct_only = !app_state.direct_only;
+                        app_state.scroll_offset = 0;
+                        app_state.selected_index = None;
+                    },
+                    KeyCode::Char('f') if matches!(app_state.mode, AppMode::List) => {
+                        app_state.cycle_kind_filter();
+                        app_state.scroll_offset = 0;
+                        app_state.selected_index = None;
+                    },
+                    _ => {}
+                },
+                AppMode::Detail => match key.code {
+                    KeyCode::Char('q') => break,
+                    KeyCode::Esc => app_state.mode = AppMode::List,
+                    _ => {}
+                },
+            }
+        }
+    }
+
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    if let Some(err) = scan_error {
+        return Err(err);
+    }
+    for warning in &app_state.warnings {
+        eprintln!("node-size: warning: {}", warning);
+    }
+    for error in &app_state.errors {
+        eprintln!("node-size: warning: {}", error);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    
+    fn module(name: &str, size: u64) -> ModuleInfo {
+        ModuleInfo {
+            name: name.to_string(),
+            size,
+            path: format!("node_modules/{}", name),
+            ..ModuleInfo::default()
+        }
+    }
+    
+    #[test]
+    fn test_rows_grouped_by_scope() {
+        let mut app_state = AppState::new(vec![
+            module("lodash", 400),
+            module("@babel/core", 300),
+            module("@babel/parser", 200),
+            module("@types/node", 50),
+        ]);
+        app_state.group_by_scope = true;
+        
+        // Grouping puts @babel (500 B) ahead of lodash (400 B)
+        let rows = app_state.rows();
+        assert_eq!(rows.len(), 6);
+        match &rows[0] {
+            ListRow::Scope { name, size, count } => {
+                assert_eq!(name, "@babel");
+                assert_eq!(*size, 500);
+                assert_eq!(*count, 2);
+            },
+            _ => panic!("Expected @babel scope row"),
+        }
+        assert!(matches!(rows[3], ListRow::Module(0)));
+
+        // By disk usage the many small files of lodash outweigh @babel
+        for m in &mut app_state.modules {
+            m.disk_size = if m.name == "lodash" { 40960 } else { 4096 };
+        }
+        app_state.toggle_metric();
+        let rows = app_state.rows();
+        assert!(matches!(rows[0], ListRow::Module(0)));
+        assert!(matches!(rows[1], ListRow::Scope { size: 8192, .. }));
+    }
+    
+    #[test]
+    fn test_rows_filtered_by_kind() {
+        let mut modules = vec![module("react", 400), module("jest", 300), module("fsevents", 200), module("left-pad", 10)];
+        modules[0].dependency_kind = Some(DependencyKind::Prod);
+        modules[1].dependency_kind = Some(DependencyKind::Dev);
+        modules[2].dependency_kind = Some(DependencyKind::Optional);
+        let mut app_state = AppState::new(modules);
+        assert_eq!(app_state.rows().len(), 4);
+
+        app_state.cycle_kind_filter();
+        assert_eq!(app_state.kind_filter, Some(DependencyKind::Prod));
+        assert!(matches!(app_state.rows()[..], [ListRow::Module(0)]));
+
+        app_state.cycle_kind_filter();
+        assert!(matches!(app_state.rows()[..], [ListRow::Module(1)]));
+
+        app_state.cycle_kind_filter();
+        app_state.cycle_kind_filter();
+        assert_eq!(app_state.kind_filter, Some(DependencyKind::Peer));
+        assert!(app_state.rows().is_empty());
+
+        app_state.cycle_kind_filter();
+        assert_eq!(app_state.kind_filter, None);
+        assert_eq!(app_state.rows().len(), 4);
+    }
+    
+    #[test]
+    fn test_direct_dependencies_only() {
+        use crate::lockfile::from_installed;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let project = temp_dir.path();
+        fs::create_dir_all(project.join("node_modules/b")).unwrap();
+        fs::create_dir_all(project.join("node_modules/a")).unwrap();
+        fs::write(project.join("package.json"), r#"{"dependencies": {"a": "*"}}"#).unwrap();
+        fs::write(project.join("node_modules/a/package.json"), r#"{"dependencies": {"b": "*"}}"#).unwrap();
+        fs::write(project.join("node_modules/b/index.js"), [b'b'; 100]).unwrap();
+
+        let modules = crate::scanner::scan_modules_dir(&project.join("node_modules"), &Default::default()).unwrap().modules;
+        let graph = from_installed(project, &modules).unwrap();
+        let mut app_state = AppState::new(modules);
+        app_state.set_graph(graph);
+        app_state.direct_only = true;
+
+        let rows = app_state.rows();
+        assert_eq!(rows.len(), 1);
+        let ListRow::Module(a) = rows[0] else {
+            panic!("Expected module row");
+        };
+        let retained = app_state.retained_size(&app_state.modules[a]).unwrap();
+        assert_eq!(retained.own, app_state.modules[a].size);
+        assert_eq!(retained.closure, retained.own + 100);
+        assert_eq!(retained.exclusive, retained.closure);
+
+        // The columns follow the metric, disk usage counts the directories too
+        app_state.toggle_metric();
+        let a = app_state.modules.iter().find(|m| m.name == "a").unwrap();
+        let b = app_state.modules.iter().find(|m| m.name == "b").unwrap();
+        let retained = app_state.retained_size(a).unwrap();
+        assert_eq!(retained.own, a.disk_size);
+        assert_eq!(retained.closure, a.disk_size + b.disk_size);
+        assert_ne!(retained.closure, a.size + b.size);
+    }
+    
+    #[test]
+    fn test_duplicate_rows() {
+        let mut nested = module("lodash", 300);
+        nested.path = "node_modules/a/node_modules/lodash".to_string();
+        nested.parent = Some("node_modules/a".to_string());
+        let app_state = AppState::new(vec![module("lodash", 500), module("a", 10), nested]);
+
+        assert_eq!(app_state.duplicates.len(), 1);
+        let rows = app_state.duplicate_rows();
+        assert_eq!(rows.len(), 3);
+        assert!(matches!(rows[0], DuplicateRow::Set(0)));
+        assert!(matches!(rows[2], DuplicateRow::Copy(0, 1)));
+        assert_eq!(app_state.duplicates[0].copies[1].dependents, vec!["a"]);
+    }
+    
+    #[test]
+    fn test_toggle_metric() {
+        let mut many_files = module("many-files", 100);
+        many_files.disk_size = 40960;
+        let mut one_file = module("one-file", 1000);
+        one_file.disk_size = 4096;
+        let mut app_state = AppState::new(vec![one_file, many_files]);
+
+        app_state.toggle_metric();
+        assert_eq!(app_state.metric, SizeMetric::Disk);
+        assert_eq!(app_state.modules[0].name, "many-files");
+
+        app_state.toggle_metric();
+        assert_eq!(app_state.modules[0].name, "one-file");
+    }
+    
+    #[test]
+    fn test_sort_columns() {
+        let mut modules = vec![module("b", 300), module("a", 200), module("c", 100)];
+        modules[0].version = Some("1.10.0".to_string());
+        modules[1].version = Some("1.9.0".to_string());
+        modules[0].files_count = Some(5);
+        modules[2].files_count = Some(50);
+        let mut app_state = AppState::new(modules);
+        let names = |app_state: &AppState| app_state.modules.iter().map(|m| m.name.clone()).collect::<Vec<_>>();
+
+        app_state.cycle_sort();
+        assert_eq!((app_state.sort, app_state.sort_descending), (SortColumn::Name, false));
+        assert_eq!(names(&app_state), ["a", "b", "c"]);
+        app_state.reverse_sort();
+        assert_eq!(names(&app_state), ["c", "b", "a"]);
+
+        // Numeric version parts, and modules without a version last either way
+        app_state.cycle_sort();
+        assert_eq!(names(&app_state), ["a", "b", "c"]);
+        app_state.reverse_sort();
+        assert_eq!(names(&app_state), ["b", "a", "c"]);
+
+        app_state.cycle_sort();
+        app_state.cycle_sort();
+        assert_eq!((app_state.sort, app_state.sort_descending), (SortColumn::Files, true));
+        assert_eq!(names(&app_state), ["c", "b", "a"]);
+
+        // Packages streamed in during a scan land in sort order
+        app_state.receive(vec![ScanEvent::Measured(Box::new(ModuleInfo {
+            files_count: Some(10),
+            ..module("d", 1)
+        }))].into_iter());
+        assert_eq!(names(&app_state), ["c", "d", "b", "a"]);
+    }
+
+    #[test]
+    fn test_search() {
+        let mut modules = vec![module("react", 400), module("@babel/core", 300), module("react-dom", 200), module("lodash", 100)];
+        modules[3].license = Some("MIT".to_string());
+        let mut app_state = AppState::new(modules);
+        app_state.group_by_scope = true;
+
+        app_state.set_search("rea".to_string());
+        assert!(app_state.searching);
+        assert_eq!(app_state.rows().len(), 2);
+
+        // An incomplete filter keeps the last search that parsed
+        app_state.set_search("rea size>".to_string());
+        assert!(app_state.search_error.is_some());
+        assert_eq!(app_state.rows().len(), 2);
+        app_state.set_search("rea size>250".to_string());
+        assert_eq!(app_state.rows().len(), 1);
+
+        app_state.set_search("license:mit".to_string());
+        assert_eq!(app_state.rows().len(), 1);
+        app_state.clear_search();
+        assert_eq!(app_state.rows().len(), 5);
+
+        // n and N skip the @babel scope header and wrap around
+        app_state.jump_to_match(true, 10);
+        assert_eq!(app_state.selected_module().map(|m| m.name.as_str()), Some("react"));
+        app_state.jump_to_match(true, 10);
+        assert_eq!(app_state.selected_index, Some(2));
+        app_state.jump_to_match(false, 10);
+        app_state.jump_to_match(false, 10);
+        assert_eq!(app_state.selected_module().map(|m| m.name.as_str()), Some("lodash"));
+        app_state.jump_to_match(true, 2);
+        assert_eq!((app_state.selected_index, app_state.scroll_offset), (Some(0), 0));
+    }
+
+    #[test]
+    fn test_background_scan_updates() {
+        let mut app_state = AppState::new(Vec::new());
+        app_state.scan = Some(ScanStatus::default());
+        let measured = |name, size| ScanEvent::Measured(Box::new(module(name, size)));
+        app_state.receive(vec![ScanEvent::Listed(3), measured("small", 10)].into_iter());
+        app_state.selected_index = Some(0);
+
+        // Bigger packages coming in move the selection along with its module
+        app_state.receive(vec![measured("big", 500), measured("mid", 50)].into_iter());
+        let names: Vec<&str> = app_state.modules.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, vec!["big", "mid", "small"]);
+        assert_eq!(app_state.selected_module().map(|m| m.name.as_str()), Some("small"));
+        let status = app_state.scan.as_ref().unwrap();
+        assert_eq!((status.packages, status.measured, status.bytes), (Some(3), 3, 560));
+
+        let mut mid = module("mid", 50);
+        mid.nested_size = 10;
+        app_state.finish_scan(Project {
+            modules: vec![module("big", 500), mid, module("small", 10)],
+            graph: None,
+            warnings: vec!["broken lockfile".to_string()],
+            errors: vec![ScanError {
+                path: "node_modules/mid/lib".to_string(),
+                message: "Permission denied (os error 13)".to_string(),
+            }],
+        });
+        assert!(app_state.scan.is_none());
+        assert_eq!(app_state.modules[1].nested_size, 10);
+        assert_eq!(app_state.selected_index, Some(2));
+        assert_eq!(app_state.warnings, vec!["broken lockfile"]);
+        assert_eq!(app_state.errors[0].to_string(), "cannot read node_modules/mid/lib: Permission denied (os error 13)");
+    }
+    
+    #[test]
+    fn test_app_state_init() {
+        let app_state = AppState::new(vec![module("test1", 100), module("test2", 200)]);
+        
+        // Check initial state
+        assert_eq!(app_state.modules.len(), 2);
+        assert_eq!(app_state.scroll_offset, 0);
+        assert_eq!(app_state.selected_index, None);
+        
+        // Check that we're in list mode
+        match app_state.mode {
+            AppMode::List => {},
+            _ => panic!("Expected AppMode::List"),
+        }
+    }
+}